@@ -1,81 +1,889 @@
+use crate::config_file::ConfigFile;
 use crate::error::GropeError;
+use encoding_rs::Encoding;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::thread;
+
+#[cfg(unix)]
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+
+/// How the pattern given on the command line is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternSyntax {
+    /// Extended regular expressions (`-E`), the default.
+    Extended,
+    /// Fixed strings matched literally (`-F`).
+    Fixed,
+    /// Basic POSIX regular expressions (`-G`).
+    Basic,
+}
+
+/// Which symbolic links are followed while walking directories recursively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Never follow symbolic links, not even when named on the command line.
+    Never,
+    /// Follow links named on the command line but not ones found while walking (`-r`).
+    CommandLine,
+    /// Follow every symbolic link (`-R`).
+    Always,
+}
+
+/// How files that look binary are handled (`--binary-files`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryFiles {
+    /// Print a single "Binary file X matches" line instead of the matching lines.
+    Binary,
+    /// Search binary files as if they were text (`-a`).
+    Text,
+    /// Assume binary files never match (`-I`).
+    WithoutMatch,
+}
+
+/// Which file names `-l` and `-L` print instead of matching lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFiles {
+    /// Files with at least one selected line (`-l`).
+    WithMatches,
+    /// Files without any selected line (`-L`).
+    WithoutMatch,
+}
+
+/// How `--column` counts the position of a match in its line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnUnit {
+    /// Bytes of the input, like `-b`, the default.
+    Bytes,
+    /// Characters, so that a column is the same whatever the file's encoding.
+    Chars,
+}
+
+/// When `--color` highlights output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
 
 #[derive(Debug)]
 pub struct Config {
-    pub pattern: String,
+    /// Every pattern to search for; a line is selected if any of them matches.
+    pub patterns: Vec<String>,
+    pub syntax: PatternSyntax,
     pub paths: Vec<PathBuf>,
     pub case_sensitive: bool,
+    /// Ignore case unless a pattern contains an uppercase letter (`-S`).
+    pub smart_case: bool,
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    /// Let patterns match across lines (`-U`).
+    pub multiline: bool,
+    /// With `-U`, let `.` match newlines too (`--multiline-dotall`).
+    pub multiline_dotall: bool,
     pub invert_match: bool,
     pub line_numbers: bool,
+    pub byte_offset: bool,
+    /// Print the 1-based column of the first match, counted in this unit (`--column`).
+    pub column: Option<ColumnUnit>,
+    /// Print `path:line:column:text` once for every match (`--vimgrep`).
+    pub vimgrep: bool,
+    pub only_matching: bool,
+    /// Template printed in place of each match (`--replace`); files are never modified.
+    pub replace: Option<String>,
+    /// Apply `--replace` to the files themselves (`--write`).
+    pub write: bool,
+    /// Print what `--write` would change as a unified diff instead (`--dry-run`).
+    pub dry_run: bool,
     pub count_only: bool,
+    pub list_files: Option<ListFiles>,
+    /// Print nothing and stop at the first selected line (`-q`).
+    pub quiet: bool,
+    /// Stop reading a file after this many selected lines, or matches with `-U` (`-m`).
+    pub max_count: Option<usize>,
+    /// Suppress messages about files that cannot be read (`-s`).
+    pub no_messages: bool,
+    pub recursive: bool,
+    pub symlinks: SymlinkPolicy,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub exclude_dir: Vec<String>,
+    /// Search only files of these types (`-t`).
+    pub types: Vec<String>,
+    /// Skip files of these types (`-T`).
+    pub types_not: Vec<String>,
+    /// `NAME:GLOB` definitions added to the built-in file types (`--type-add`).
+    pub type_add: Vec<String>,
+    /// Print the file types and exit (`--type-list`).
+    pub type_list: bool,
+    pub max_depth: Option<usize>,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub color: ColorChoice,
+    pub threads: usize,
+    pub unordered: bool,
+    pub binary_files: BinaryFiles,
+    /// Search inside gzip, xz, bzip2, zstd and lz4 files (`-z`).
+    pub search_zip: bool,
+    /// Encoding to transcode files from when they have no byte order mark (`--encoding`).
+    pub encoding: Option<&'static Encoding>,
+    pub json: bool,
+    /// Print totals for the whole run after the results (`--stats`).
+    pub stats: bool,
+    /// Follow file names with a NUL byte instead of `:` or a newline (`-Z`).
+    pub null: bool,
+    /// Read and print NUL-terminated records instead of lines (`--null-data`).
+    pub null_data: bool,
 }
 
 impl Config {
     pub fn new() -> Self {
         Self {
-            pattern: String::new(),
+            patterns: Vec::new(),
+            syntax: PatternSyntax::Extended,
             paths: Vec::new(),
             case_sensitive: true,
+            smart_case: false,
+            word_regexp: false,
+            line_regexp: false,
+            multiline: false,
+            multiline_dotall: false,
             invert_match: false,
             line_numbers: false,
+            byte_offset: false,
+            column: None,
+            vimgrep: false,
+            only_matching: false,
+            replace: None,
+            write: false,
+            dry_run: false,
             count_only: false,
+            list_files: None,
+            quiet: false,
+            max_count: None,
+            no_messages: false,
+            recursive: false,
+            symlinks: SymlinkPolicy::CommandLine,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            exclude_dir: Vec::new(),
+            types: Vec::new(),
+            types_not: Vec::new(),
+            type_add: Vec::new(),
+            type_list: false,
+            max_depth: None,
+            before_context: 0,
+            after_context: 0,
+            color: ColorChoice::Auto,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            unordered: false,
+            binary_files: BinaryFiles::Binary,
+            search_zip: false,
+            encoding: None,
+            json: false,
+            stats: false,
+            null: false,
+            null_data: false,
+        }
+    }
+
+    /// Files are rewritten, or diffed with `--dry-run`, instead of searched.
+    pub fn rewrites(&self) -> bool {
+        self.write || self.dry_run
+    }
+
+    /// The byte that ends each input and output record.
+    pub fn line_terminator(&self) -> u8 {
+        if self.null_data { 0 } else { b'\n' }
+    }
+
+    pub fn has_context(&self) -> bool {
+        self.before_context > 0 || self.after_context > 0
+    }
+
+    /// Matches are prefixed with their file name when more than one file can be searched,
+    /// and always with `--vimgrep`.
+    pub fn with_filename(&self) -> bool {
+        self.paths.len() > 1 || self.recursive || self.vimgrep
+    }
+
+    /// Starts a configuration for using grope as a library, without going through argv.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::new()
+    }
+
+    /// Rejects option combinations that cannot work together.
+    fn validate(&self) -> Result<(), GropeError> {
+        if self.rewrites() && self.replace.is_none() {
+            let flag = if self.write { "--write" } else { "--dry-run" };
+            return Err(GropeError::InvalidArgument(format!("{} requires --replace", flag)));
+        }
+        if !self.rewrites() {
+            return Ok(());
+        }
+        // Rewriting replaces every match in every line, and prints only a diff or summary.
+        let conflicts = [
+            (self.invert_match, "--invert-match"),
+            (self.only_matching, "--only-matching"),
+            (self.count_only, "--count"),
+            (self.list_files == Some(ListFiles::WithMatches), "--files-with-matches"),
+            (self.list_files == Some(ListFiles::WithoutMatch), "--files-without-match"),
+            (self.max_count.is_some(), "--max-count"),
+            (self.json, "--json"),
+            // -q stops at the first file with a match, and -z files cannot be written back.
+            (self.quiet, "--quiet"),
+            (self.search_zip, "--search-zip"),
+            (self.multiline, "--multiline"),
+            (self.null_data, "--null-data"),
+            // Replacements are written back as UTF-8.
+            (self.encoding.is_some(), "--encoding"),
+        ];
+        match conflicts.iter().find(|(set, _)| *set) {
+            Some((_, flag)) => Err(GropeError::InvalidArgument(format!(
+                "{} cannot be combined with --write or --dry-run",
+                flag
+            ))),
+            None => Ok(()),
         }
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds a `Config` from code, with the same defaults as the command line except that
+/// colors are off, since a library's output rarely goes straight to a terminal.
+///
+/// ```
+/// use grope::Config;
+///
+/// let config = Config::builder().pattern("fn main").fixed_strings(true).build()?;
+/// assert!(config.case_sensitive);
+/// # Ok::<(), grope::GropeError>(())
+/// ```
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        Self { config: Config { color: ColorChoice::Never, ..Config::new() } }
+    }
+
+    /// Adds a pattern; a line is selected if any of them matches.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.config.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn syntax(mut self, syntax: PatternSyntax) -> Self {
+        self.config.syntax = syntax;
+        self
+    }
+
+    /// Shorthand for `syntax(PatternSyntax::Fixed)`, or back to the default extended syntax.
+    pub fn fixed_strings(self, yes: bool) -> Self {
+        self.syntax(if yes { PatternSyntax::Fixed } else { PatternSyntax::Extended })
+    }
+
+    pub fn case_sensitive(mut self, yes: bool) -> Self {
+        self.config.case_sensitive = yes;
+        self.config.smart_case = false;
+        self
+    }
+
+    /// Ignores case unless a pattern contains an uppercase letter.
+    pub fn smart_case(mut self, yes: bool) -> Self {
+        self.config.smart_case = yes;
+        self
+    }
+
+    pub fn word_regexp(mut self, yes: bool) -> Self {
+        self.config.word_regexp = yes;
+        self
+    }
+
+    pub fn line_regexp(mut self, yes: bool) -> Self {
+        self.config.line_regexp = yes;
+        self
+    }
+
+    pub fn multiline(mut self, yes: bool) -> Self {
+        self.config.multiline = yes;
+        self
+    }
+
+    /// With `multiline`, lets `.` match newlines too.
+    pub fn multiline_dotall(mut self, yes: bool) -> Self {
+        self.config.multiline_dotall = yes;
+        self
+    }
+
+    pub fn invert_match(mut self, yes: bool) -> Self {
+        self.config.invert_match = yes;
+        self
+    }
+
+    pub fn max_count(mut self, max_count: Option<usize>) -> Self {
+        self.config.max_count = max_count;
+        self
+    }
+
+    pub fn before_context(mut self, lines: usize) -> Self {
+        self.config.before_context = lines;
+        self
+    }
+
+    pub fn after_context(mut self, lines: usize) -> Self {
+        self.config.after_context = lines;
+        self
+    }
+
+    /// Sets both the before and after context, like `-C`.
+    pub fn context(self, lines: usize) -> Self {
+        self.before_context(lines).after_context(lines)
+    }
+
+    pub fn line_numbers(mut self, yes: bool) -> Self {
+        self.config.line_numbers = yes;
+        self
+    }
+
+    pub fn byte_offset(mut self, yes: bool) -> Self {
+        self.config.byte_offset = yes;
+        self
+    }
+
+    /// Prints the column of the first match, counted in `unit`, or no column for `None`.
+    pub fn column(mut self, unit: Option<ColumnUnit>) -> Self {
+        self.config.column = unit;
+        self
+    }
+
+    pub fn vimgrep(mut self, yes: bool) -> Self {
+        self.config.vimgrep = yes;
+        self
+    }
+
+    pub fn only_matching(mut self, yes: bool) -> Self {
+        self.config.only_matching = yes;
+        self
+    }
+
+    pub fn replace(mut self, template: impl Into<String>) -> Self {
+        self.config.replace = Some(template.into());
+        self
+    }
+
+    pub fn count_only(mut self, yes: bool) -> Self {
+        self.config.count_only = yes;
+        self
+    }
+
+    pub fn list_files(mut self, list_files: Option<ListFiles>) -> Self {
+        self.config.list_files = list_files;
+        self
+    }
+
+    pub fn binary_files(mut self, binary_files: BinaryFiles) -> Self {
+        self.config.binary_files = binary_files;
+        self
+    }
+
+    pub fn search_zip(mut self, yes: bool) -> Self {
+        self.config.search_zip = yes;
+        self
+    }
+
+    /// Searches NUL-terminated records instead of lines.
+    pub fn null_data(mut self, yes: bool) -> Self {
+        self.config.null_data = yes;
+        self
+    }
+
+    /// The encoding of inputs without a byte order mark, or `None` to search raw bytes.
+    pub fn encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.config.encoding = encoding;
+        self
+    }
+
+    pub fn color(mut self, color: ColorChoice) -> Self {
+        self.config.color = color;
+        self
+    }
+
+    pub fn json(mut self, yes: bool) -> Self {
+        self.config.json = yes;
+        self
+    }
+
+    /// Adds a file or directory for `Pool` to search; a `Searcher` is handed its inputs
+    /// directly and ignores these.
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.paths.push(path.into());
+        self
+    }
+
+    /// Restricts recursive searches to files of type `name`, such as `rust`.
+    pub fn file_type(mut self, name: impl Into<String>) -> Self {
+        self.config.types.push(name.into());
+        self
+    }
+
+    pub fn recursive(mut self, yes: bool) -> Self {
+        self.config.recursive = yes;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = threads.max(1);
+        self
+    }
+
+    /// Checks the options the same way the command line does.
+    pub fn build(self) -> Result<Config, GropeError> {
+        let config = self.config;
+        if config.patterns.is_empty() {
+            return Err(GropeError::NoPattern);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn parse_args<I>(args: I) -> Result<Config, GropeError>
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    parse_args_with_config(None, args)
+}
+
+/// Parses `args` after the arguments of `config_file`, so that the command line overrides
+/// them. Errors in the file's arguments name the file and line they came from.
+pub fn parse_args_with_config<I>(
+    config_file: Option<&ConfigFile>,
+    args: I,
+) -> Result<Config, GropeError>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let file_args = config_file.into_iter().flat_map(|file| file.args().map(str::to_string));
+    let command_line = args.into_iter().skip(1).map(|arg| arg.as_ref().to_string());
+    let args: Vec<String> = file_args.chain(command_line).collect();
+
+    let mut position = 0;
+    parse(args, &mut position).map_err(|err| match config_file {
+        Some(file) if position < file.len() => file.error_at(position, err),
+        _ => err,
+    })
+}
+
+/// Parses the arguments, keeping `position` at the index of the one being parsed, or past
+/// the end once errors are no longer about a single argument.
+fn parse(args: Vec<String>, position: &mut usize) -> Result<Config, GropeError> {
     let mut config = Config::new();
-    let args: Vec<_> = args.into_iter().skip(1).collect();
+    // -A and -B win over -C regardless of order, as in GNU grep.
+    let (mut after, mut before, mut context) = (None, None, None);
+    // With -e or -f, every positional argument is a path.
+    let mut patterns_given = false;
+    // --files-from with an empty list searches nothing rather than stdin.
+    let mut paths_given = false;
+    let mut positional = Vec::new();
 
     let mut i = 0;
     while i < args.len() {
-        match args[i].as_ref() {
-            "-i" | "--ignore-case" => config.case_sensitive = false,
+        *position = i;
+        let (flag, inline_value) = split_flag(&args[i]);
+        match flag {
+            "-E" | "--extended-regexp" => config.syntax = PatternSyntax::Extended,
+            "-F" | "--fixed-strings" => config.syntax = PatternSyntax::Fixed,
+            "-G" | "--basic-regexp" => config.syntax = PatternSyntax::Basic,
+            // -i and -S override each other, so the last one given wins.
+            "-i" | "--ignore-case" => {
+                config.case_sensitive = false;
+                config.smart_case = false;
+            }
+            "-S" | "--smart-case" => config.smart_case = true,
+            "-w" | "--word-regexp" => config.word_regexp = true,
+            "-x" | "--line-regexp" => config.line_regexp = true,
+            "-U" | "--multiline" => config.multiline = true,
+            "--multiline-dotall" => config.multiline_dotall = true,
             "-v" | "--invert-match" => config.invert_match = true,
             "-n" | "--line-number" => config.line_numbers = true,
+            "-b" | "--byte-offset" => config.byte_offset = true,
+            "--column" => {
+                // A bare --column counts bytes, like -b.
+                config.column = match inline_value {
+                    None | Some("bytes") => Some(ColumnUnit::Bytes),
+                    Some("chars") => Some(ColumnUnit::Chars),
+                    Some(value) => return Err(invalid_value(flag, value)),
+                };
+            }
+            "--vimgrep" => config.vimgrep = true,
+            "-o" | "--only-matching" => config.only_matching = true,
+            "--replace" => config.replace = Some(take_value(flag, inline_value, &args, &mut i)?),
+            "--write" => config.write = true,
+            "--dry-run" => config.dry_run = true,
             "-c" | "--count" => config.count_only = true,
+            "-l" | "--files-with-matches" => config.list_files = Some(ListFiles::WithMatches),
+            "-L" | "--files-without-match" => config.list_files = Some(ListFiles::WithoutMatch),
+            "-q" | "--quiet" | "--silent" => config.quiet = true,
+            "-m" | "--max-count" => {
+                config.max_count = Some(take_number(flag, inline_value, &args, &mut i)?);
+            }
+            "-s" | "--no-messages" => config.no_messages = true,
+            "-r" | "--recursive" => {
+                config.recursive = true;
+                config.symlinks = SymlinkPolicy::CommandLine;
+            }
+            "-R" | "--dereference-recursive" => {
+                config.recursive = true;
+                config.symlinks = SymlinkPolicy::Always;
+            }
+            "--symlinks" => {
+                config.symlinks = match take_value(flag, inline_value, &args, &mut i)?.as_str() {
+                    "never" => SymlinkPolicy::Never,
+                    "command-line" => SymlinkPolicy::CommandLine,
+                    "always" => SymlinkPolicy::Always,
+                    value => return Err(invalid_value(flag, value)),
+                };
+            }
+            "--include" => config.include.push(take_value(flag, inline_value, &args, &mut i)?),
+            "--exclude" => config.exclude.push(take_value(flag, inline_value, &args, &mut i)?),
+            "--exclude-dir" => {
+                config.exclude_dir.push(take_value(flag, inline_value, &args, &mut i)?)
+            }
+            "-t" | "--type" => config.types.push(take_value(flag, inline_value, &args, &mut i)?),
+            "-T" | "--type-not" => {
+                config.types_not.push(take_value(flag, inline_value, &args, &mut i)?)
+            }
+            "--type-add" => config.type_add.push(take_value(flag, inline_value, &args, &mut i)?),
+            "--type-list" => config.type_list = true,
+            "--max-depth" => {
+                config.max_depth = Some(take_number(flag, inline_value, &args, &mut i)?);
+            }
+            "-A" | "--after-context" => {
+                after = Some(take_number(flag, inline_value, &args, &mut i)?);
+            }
+            "-B" | "--before-context" => {
+                before = Some(take_number(flag, inline_value, &args, &mut i)?);
+            }
+            "-C" | "--context" => {
+                context = Some(take_number(flag, inline_value, &args, &mut i)?);
+            }
+            "--color" | "--colour" => {
+                // A bare --color means auto, as in GNU grep.
+                config.color = match inline_value {
+                    None | Some("auto") | Some("tty") | Some("if-tty") => ColorChoice::Auto,
+                    Some("always") | Some("yes") | Some("force") => ColorChoice::Always,
+                    Some("never") | Some("no") | Some("none") => ColorChoice::Never,
+                    Some(value) => return Err(invalid_value(flag, value)),
+                };
+            }
+            "-j" | "--threads" => {
+                config.threads = match take_number(flag, inline_value, &args, &mut i)? {
+                    0 => return Err(invalid_value(flag, "0")),
+                    threads => threads,
+                };
+            }
+            "--unordered" => config.unordered = true,
+            "--binary-files" => {
+                let value = take_value(flag, inline_value, &args, &mut i)?;
+                config.binary_files = match value.as_str() {
+                    "binary" => BinaryFiles::Binary,
+                    "text" => BinaryFiles::Text,
+                    "without-match" => BinaryFiles::WithoutMatch,
+                    value => return Err(invalid_value(flag, value)),
+                };
+            }
+            "-a" | "--text" => config.binary_files = BinaryFiles::Text,
+            "-I" => config.binary_files = BinaryFiles::WithoutMatch,
+            "-z" | "--search-zip" => config.search_zip = true,
+            "--encoding" => {
+                let value = take_value(flag, inline_value, &args, &mut i)?;
+                config.encoding = match value.as_str() {
+                    "auto" => None,
+                    label => Some(
+                        Encoding::for_label(label.as_bytes())
+                            .ok_or_else(|| invalid_value(flag, &value))?,
+                    ),
+                };
+            }
+            "--json" => config.json = true,
+            "--stats" => config.stats = true,
+            "-Z" | "--null" => config.null = true,
+            "--null-data" => config.null_data = true,
+            "--files-from" | "--files0-from" => {
+                let file = take_value(flag, inline_value, &args, &mut i)?;
+                let terminator = if flag == "--files0-from" { 0 } else { b'\n' };
+                config.paths.extend(read_paths(&file, terminator)?);
+                paths_given = true;
+            }
+            // Handled by `ConfigFile::load` before parsing.
+            "--no-config" => {}
+            "-e" | "--regexp" => {
+                config.patterns.push(take_value(flag, inline_value, &args, &mut i)?);
+                patterns_given = true;
+            }
+            "-f" | "--file" => {
+                let file = take_value(flag, inline_value, &args, &mut i)?;
+                config.patterns.extend(read_patterns(&file)?);
+                patterns_given = true;
+            }
             "-h" | "--help" => {
                 print_help();
                 std::process::exit(0);
             }
-            arg if arg.starts_with('-') => {
+            arg if arg.starts_with('-') && arg != "-" => {
                 return Err(GropeError::InvalidArgument(arg.to_string()))
             }
-            _ => {
-                if config.pattern.is_empty() {
-                    config.pattern = args[i].as_ref().to_string();
-                } else {
-                    config.paths.push(PathBuf::from(args[i].as_ref()));
-                }
-            }
+            arg => positional.push(arg.to_string()),
         }
         i += 1;
     }
+    *position = args.len();
 
-    if config.pattern.is_empty() {
-        return Err(GropeError::NoPattern);
+    let mut positional = positional.into_iter();
+    // --type-list needs no pattern, only the --type-add definitions.
+    if !patterns_given && !config.type_list {
+        config.patterns.push(positional.next().ok_or(GropeError::NoPattern)?);
     }
+    config.paths.extend(positional.map(PathBuf::from));
 
-    if config.paths.is_empty() {
-        config.paths.push(PathBuf::from("-"));  // stdin
+    config.validate()?;
+
+    config.after_context = after.or(context).unwrap_or(0);
+    config.before_context = before.or(context).unwrap_or(0);
+
+    if config.paths.is_empty() && !paths_given {
+        if config.recursive {
+            config.paths.push(PathBuf::from("."));
+        } else {
+            config.paths.push(PathBuf::from("-"));  // stdin
+        }
     }
 
     Ok(config)
 }
 
+/// Short options that take a value, which may be attached as in `-A3`.
+const SHORT_VALUE_FLAGS: &[&str] = &["-A", "-B", "-C", "-T", "-e", "-f", "-j", "-m", "-t"];
+
+/// Long options that take a value, which may be attached as in `--context=3`.
+const LONG_VALUE_FLAGS: &[&str] = &[
+    "--after-context",
+    "--before-context",
+    "--binary-files",
+    "--context",
+    "--encoding",
+    "--exclude",
+    "--exclude-dir",
+    "--file",
+    "--files-from",
+    "--files0-from",
+    "--include",
+    "--max-count",
+    "--max-depth",
+    "--regexp",
+    "--replace",
+    "--symlinks",
+    "--threads",
+    "--type",
+    "--type-add",
+    "--type-not",
+];
+
+/// Whether `args`, the command line after the program name, gives `--no-config` as a flag
+/// rather than as the value of the option before it.
+pub fn has_no_config<S: AsRef<str>>(args: &[S]) -> bool {
+    let mut args = args.iter().map(AsRef::as_ref);
+    while let Some(arg) = args.next() {
+        match split_flag(arg) {
+            ("--no-config", _) => return true,
+            (flag, None)
+                if SHORT_VALUE_FLAGS.contains(&flag) || LONG_VALUE_FLAGS.contains(&flag) =>
+            {
+                args.next();
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Reads one pattern per line from `file`, or from stdin when it is `-`.
+fn read_patterns(file: &str) -> Result<Vec<String>, GropeError> {
+    let content = if file == "-" {
+        io::read_to_string(io::stdin())?
+    } else {
+        fs::read_to_string(file)?
+    };
+    Ok(content.lines().map(str::to_string).collect())
+}
+
+/// Reads the paths to search from `file`, or from stdin when it is `-`, one per
+/// `terminator`-ended entry.
+fn read_paths(file: &str, terminator: u8) -> Result<Vec<PathBuf>, GropeError> {
+    let content = if file == "-" {
+        let mut content = Vec::new();
+        io::Read::read_to_end(&mut io::stdin(), &mut content)?;
+        content
+    } else {
+        fs::read(file)?
+    };
+    let entries = content.split(|&byte| byte == terminator);
+    Ok(entries
+        .map(|entry| match terminator {
+            b'\n' => entry.strip_suffix(b"\r").unwrap_or(entry),
+            _ => entry,
+        })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Splits `--flag=value` and `-Xvalue` into the flag and its inline value.
+fn split_flag(arg: &str) -> (&str, Option<&str>) {
+    if arg.starts_with("--") {
+        return match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value)),
+            None => (arg, None),
+        };
+    }
+    match arg.get(..2) {
+        Some(flag) if arg.len() > 2 && SHORT_VALUE_FLAGS.contains(&flag) => (flag, Some(&arg[2..])),
+        _ => (arg, None),
+    }
+}
+
+/// Returns the value of `flag`, either given inline or as the next argument.
+fn take_value<S: AsRef<str>>(
+    flag: &str,
+    inline_value: Option<&str>,
+    args: &[S],
+    i: &mut usize,
+) -> Result<String, GropeError> {
+    if let Some(value) = inline_value {
+        return Ok(value.to_string());
+    }
+    *i += 1;
+    args.get(*i)
+        .map(|value| value.as_ref().to_string())
+        .ok_or_else(|| GropeError::MissingValue(flag.to_string()))
+}
+
+fn take_number<S: AsRef<str>>(
+    flag: &str,
+    inline_value: Option<&str>,
+    args: &[S],
+    i: &mut usize,
+) -> Result<usize, GropeError> {
+    let value = take_value(flag, inline_value, args, i)?;
+    value.parse().map_err(|_| invalid_value(flag, &value))
+}
+
+fn invalid_value(flag: &str, value: &str) -> GropeError {
+    GropeError::InvalidValue(flag.to_string(), value.to_string())
+}
+
 fn print_help() {
     println!("Usage: grope [OPTIONS] PATTERN [FILE...]");
+    println!("       grope [OPTIONS] -e PATTERN... [-f FILE...] [FILE...]");
     println!("\nOptions:");
-    println!("  -i, --ignore-case    Ignore case distinctions");
-    println!("  -v, --invert-match   Select non-matching lines");
-    println!("  -n, --line-number    Print line number with output lines");
-    println!("  -c, --count          Print only a count of matching lines");
-    println!("  -h, --help           Print this help message");
+    println!("  -e, --regexp=PATTERN   Search for PATTERN; may be given more than once");
+    println!("  -f, --file=FILE        Read patterns from FILE, one per line");
+    println!("  -E, --extended-regexp  PATTERN is an extended regular expression (default)");
+    println!("  -F, --fixed-strings    PATTERN is a fixed string");
+    println!("  -G, --basic-regexp     PATTERN is a basic regular expression");
+    println!("  -i, --ignore-case      Ignore case distinctions");
+    println!("  -S, --smart-case       Ignore case unless PATTERN contains an uppercase letter");
+    println!("  -w, --word-regexp      Match only whole words");
+    println!("  -x, --line-regexp      Match only whole lines");
+    println!("  -U, --multiline        Let PATTERN match across lines, printing every line of");
+    println!("                         each match; a match may span up to 512 KiB");
+    println!("      --multiline-dotall With -U, let . match newlines too");
+    println!("  -v, --invert-match     Select non-matching lines");
+    println!("  -n, --line-number      Print line number with output lines");
+    println!("  -b, --byte-offset      Print the byte offset of each line, or match with -o");
+    println!("  -o, --only-matching    Print each match on its own line");
+    println!("      --column[=UNIT]    Print the column of the first match, or of each match with");
+    println!("                         -o, counted in bytes (default) or chars");
+    println!("      --vimgrep          Print path:line:column:text once for every match");
+    println!("      --replace=TEMPLATE Print TEMPLATE in place of each match; $1, ${{name}} and");
+    println!("                         $$ expand to capture groups and a literal $");
+    println!("      --write            Apply --replace to the files in place");
+    println!("      --dry-run          Print the changes --write would make as a unified diff");
+    println!("  -c, --count            Print only a count of matching lines");
+    println!("  -l, --files-with-matches");
+    println!("                         Print only the names of files with matches");
+    println!("  -L, --files-without-match");
+    println!("                         Print only the names of files without matches");
+    println!("  -q, --quiet            Print nothing; exit 0 as soon as a line is selected");
+    println!("  -m, --max-count=NUM    Stop reading a file after NUM selected lines, or NUM");
+    println!("                         matches with -U");
+    println!("  -s, --no-messages      Suppress messages about unreadable files");
+    println!("  -r, --recursive        Search directories recursively");
+    println!("  -R, --dereference-recursive");
+    println!("                         Search recursively, following all symbolic links");
+    println!("      --symlinks=WHEN    Follow symbolic links: never, command-line or always");
+    println!("      --include=GLOB     Search only files whose name matches GLOB");
+    println!("      --exclude=GLOB     Skip files whose name matches GLOB");
+    println!("      --exclude-dir=GLOB Skip directories whose name matches GLOB");
+    println!("  -t, --type=TYPE        Search only files of TYPE, such as rust, py or md");
+    println!("  -T, --type-not=TYPE    Skip files of TYPE");
+    println!("      --type-add=NAME:GLOB");
+    println!("                         Add GLOB to file type NAME, defining it if needed");
+    println!("      --type-list        Print every file type and its globs");
+    println!("      --max-depth=NUM    Descend at most NUM directory levels");
+    println!("  -A, --after-context=NUM   Print NUM lines of trailing context");
+    println!("  -B, --before-context=NUM  Print NUM lines of leading context");
+    println!("  -C, --context=NUM      Print NUM lines of leading and trailing context");
+    println!("      --color[=WHEN]     Highlight matches: auto (default), always or never");
+    println!("  -j, --threads=NUM      Search NUM files at once (default: number of CPUs)");
+    println!("      --unordered        Print results as files finish instead of in input order");
+    println!("      --binary-files=TYPE");
+    println!("                         Binary file handling: binary (default), text, without-match");
+    println!("  -a, --text             Same as --binary-files=text");
+    println!("  -I                     Same as --binary-files=without-match");
+    println!("  -z, --search-zip       Search inside gzip, xz, bzip2, zstd and lz4 files");
+    println!("      --encoding=NAME    Transcode files without a BOM from NAME, such as utf-16le,");
+    println!("                         latin1, windows-1252 or shift_jis (default: auto)");
+    println!("      --json             Print results as JSON Lines");
+    println!("      --stats            Print totals for lines, matches, files, bytes and time");
+    println!("  -Z, --null             Follow file names with a NUL byte instead of : or newline");
+    println!("      --null-data        Treat input and output as NUL-terminated records");
+    println!("      --files-from=FILE  Search the paths listed one per line in FILE (- for stdin)");
+    println!("      --files0-from=FILE Search the NUL-separated paths listed in FILE");
+    println!("      --no-config        Ignore the config file named by GROPE_CONFIG_PATH or at");
+    println!("                         $XDG_CONFIG_HOME/grope/config");
+    println!("  -h, --help             Print this help message");
 }
 
 mod tests {
@@ -86,7 +894,7 @@ mod tests {
     fn test_basic_pattern_and_path() {
         let args = vec!["grope", "pattern", "file.txt"];
         let config = parse_args(args).unwrap();
-        assert_eq!(config.pattern, "pattern");
+        assert_eq!(config.patterns, vec!["pattern"]);
         assert_eq!(config.paths, vec![PathBuf::from("file.txt")]);
     }
 
@@ -97,6 +905,335 @@ mod tests {
         assert!(!config.case_sensitive);
     }
 
+    #[test]
+    fn test_smart_case_option() {
+        assert!(parse_args(vec!["grope", "-i", "--smart-case", "p"]).unwrap().smart_case);
+
+        let config = parse_args(vec!["grope", "-S", "-i", "p"]).unwrap();
+        assert!(!config.smart_case);
+        assert!(!config.case_sensitive);
+    }
+
+    #[test]
+    fn test_pattern_syntax_options() {
+        let config = parse_args(vec!["grope", "pattern"]).unwrap();
+        assert_eq!(config.syntax, PatternSyntax::Extended);
+
+        let config = parse_args(vec!["grope", "-F", "a.b"]).unwrap();
+        assert_eq!(config.syntax, PatternSyntax::Fixed);
+
+        let config = parse_args(vec!["grope", "-F", "-G", "a.b"]).unwrap();
+        assert_eq!(config.syntax, PatternSyntax::Basic);
+    }
+
+    #[test]
+    fn test_recursive_options() {
+        let args = vec![
+            "grope", "-r", "--include=*.rs", "--exclude", "*.bak", "--exclude-dir=target",
+            "--max-depth", "2", "pattern",
+        ];
+        let config = parse_args(args).unwrap();
+        assert!(config.recursive);
+        assert_eq!(config.symlinks, SymlinkPolicy::CommandLine);
+        assert_eq!(config.include, vec!["*.rs"]);
+        assert_eq!(config.exclude, vec!["*.bak"]);
+        assert_eq!(config.exclude_dir, vec!["target"]);
+        assert_eq!(config.max_depth, Some(2));
+        assert_eq!(config.paths, vec![PathBuf::from(".")]);
+        assert!(config.with_filename());
+
+        let config = parse_args(vec!["grope", "-R", "pattern", "src"]).unwrap();
+        assert_eq!(config.symlinks, SymlinkPolicy::Always);
+    }
+
+    #[test]
+    fn test_type_options() {
+        let args = vec!["grope", "-trust", "--type=md", "-T", "py", "--type-add=x:*.x", "p"];
+        let config = parse_args(args).unwrap();
+        assert_eq!(config.types, vec!["rust", "md"]);
+        assert_eq!(config.types_not, vec!["py"]);
+        assert_eq!(config.type_add, vec!["x:*.x"]);
+
+        let config = parse_args(vec!["grope", "--type-list"]).unwrap();
+        assert!(config.type_list);
+        assert!(config.patterns.is_empty());
+    }
+
+    #[test]
+    fn test_missing_and_invalid_values() {
+        let args = vec!["grope", "pattern", "--include"];
+        assert!(matches!(parse_args(args), Err(GropeError::MissingValue(_))));
+        // `has_no_config` skips the values of these, so each must really take one.
+        for flag in SHORT_VALUE_FLAGS.iter().chain(LONG_VALUE_FLAGS) {
+            let args = vec!["grope", "pattern", flag];
+            assert!(matches!(parse_args(args), Err(GropeError::MissingValue(_))), "{}", flag);
+        }
+
+        let args = vec!["grope", "--max-depth=deep", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_context_options() {
+        let config = parse_args(vec!["grope", "-C", "2", "pattern"]).unwrap();
+        assert_eq!((config.before_context, config.after_context), (2, 2));
+
+        let config = parse_args(vec!["grope", "-A1", "--context=3", "pattern"]).unwrap();
+        assert_eq!((config.before_context, config.after_context), (3, 1));
+        assert!(config.has_context());
+    }
+
+    #[test]
+    fn test_color_option() {
+        let config = parse_args(vec!["grope", "--color=never", "pattern"]).unwrap();
+        assert_eq!(config.color, ColorChoice::Never);
+
+        let config = parse_args(vec!["grope", "--color", "pattern"]).unwrap();
+        assert_eq!(config.color, ColorChoice::Auto);
+
+        let config = parse_args(vec!["grope", "--colour=always", "pattern"]).unwrap();
+        assert_eq!(config.color, ColorChoice::Always);
+
+        let args = vec!["grope", "--color=sometimes", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_thread_options() {
+        let config = parse_args(vec!["grope", "pattern"]).unwrap();
+        assert!(config.threads >= 1);
+        assert!(!config.unordered);
+
+        let config = parse_args(vec!["grope", "-j4", "--unordered", "pattern"]).unwrap();
+        assert_eq!(config.threads, 4);
+        assert!(config.unordered);
+
+        let args = vec!["grope", "-j", "0", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_binary_files_options() {
+        let config = parse_args(vec!["grope", "pattern"]).unwrap();
+        assert_eq!(config.binary_files, BinaryFiles::Binary);
+
+        let args = vec!["grope", "--binary-files=without-match", "-a", "pattern"];
+        let config = parse_args(args).unwrap();
+        assert_eq!(config.binary_files, BinaryFiles::Text);
+
+        let config = parse_args(vec!["grope", "-I", "pattern"]).unwrap();
+        assert_eq!(config.binary_files, BinaryFiles::WithoutMatch);
+    }
+
+    #[test]
+    fn test_multiple_patterns() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"from_file\n\nlast\n").unwrap();
+        let pattern_file = file.path().to_str().unwrap();
+
+        let args = vec!["grope", "-e", "one", "-etwo", "-f", pattern_file, "a.txt", "b.txt"];
+        let config = parse_args(args).unwrap();
+        assert_eq!(config.patterns, vec!["one", "two", "from_file", "", "last"]);
+        assert_eq!(config.paths, vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]);
+
+        let config = parse_args(vec!["grope", "--regexp=-x", "file"]).unwrap();
+        assert_eq!(config.patterns, vec!["-x"]);
+        assert_eq!(config.paths, vec![PathBuf::from("file")]);
+    }
+
+    #[test]
+    fn test_output_options() {
+        let config = parse_args(vec!["grope", "-o", "--byte-offset", "pattern"]).unwrap();
+        assert!(config.only_matching);
+        assert!(config.byte_offset);
+        assert!(!config.stats);
+        assert!(parse_args(vec!["grope", "--stats", "pattern"]).unwrap().stats);
+    }
+
+    #[test]
+    fn test_column_options() {
+        let config = parse_args(vec!["grope", "pattern"]).unwrap();
+        assert_eq!(config.column, None);
+        assert!(!config.vimgrep);
+
+        let config = parse_args(vec!["grope", "--column", "--vimgrep", "pattern"]).unwrap();
+        assert_eq!(config.column, Some(ColumnUnit::Bytes));
+        assert!(config.vimgrep);
+        assert!(config.with_filename());
+
+        let config = parse_args(vec!["grope", "--column=chars", "pattern"]).unwrap();
+        assert_eq!(config.column, Some(ColumnUnit::Chars));
+
+        let args = vec!["grope", "--column=words", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_null_options() {
+        let config = parse_args(vec!["grope", "-Z", "--null-data", "pattern"]).unwrap();
+        assert!(config.null);
+        assert!(config.null_data);
+        assert_eq!(config.line_terminator(), 0);
+        assert_eq!(Config::new().line_terminator(), b'\n');
+
+        let rewrite = vec!["grope", "--null-data", "--replace=x", "--write", "pattern"];
+        assert!(matches!(parse_args(rewrite), Err(GropeError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_files_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines = dir.path().join("lines");
+        fs::write(&lines, "a.txt\r\n\nsub dir/b.txt\n").unwrap();
+        let nul = dir.path().join("nul");
+        fs::write(&nul, "new\nline\0c.txt\0").unwrap();
+        let empty = dir.path().join("empty");
+        fs::write(&empty, "").unwrap();
+
+        let lines = lines.to_str().unwrap();
+        let config = parse_args(vec!["grope", "x", "--files-from", lines, "d.txt"]).unwrap();
+        let expected = ["a.txt", "sub dir/b.txt", "d.txt"].map(PathBuf::from);
+        assert_eq!(config.paths, expected);
+
+        let nul = format!("--files0-from={}", nul.display());
+        let config = parse_args(vec!["grope", "x", &nul]).unwrap();
+        assert_eq!(config.paths, ["new\nline", "c.txt"].map(PathBuf::from));
+
+        let config = parse_args(vec!["grope", "x", "--files-from", empty.to_str().unwrap()]);
+        assert!(config.unwrap().paths.is_empty());
+
+        let missing = dir.path().join("missing");
+        let missing = vec!["grope", "x", "--files-from", missing.to_str().unwrap()];
+        assert!(matches!(parse_args(missing), Err(GropeError::Io(_))));
+    }
+
+    #[test]
+    fn test_list_files_options() {
+        let config = parse_args(vec!["grope", "-l", "pattern"]).unwrap();
+        assert_eq!(config.list_files, Some(ListFiles::WithMatches));
+
+        let config = parse_args(vec!["grope", "-l", "-L", "pattern"]).unwrap();
+        assert_eq!(config.list_files, Some(ListFiles::WithoutMatch));
+    }
+
+    #[test]
+    fn test_quiet_and_max_count_options() {
+        let config = parse_args(vec!["grope", "-q", "-m2", "-s", "pattern"]).unwrap();
+        assert!(config.quiet);
+        assert_eq!(config.max_count, Some(2));
+        assert!(config.no_messages);
+
+        let args = vec!["grope", "--max-count", "many", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_replace_option() {
+        let config = parse_args(vec!["grope", "--replace", "$1", "(a)", "file"]).unwrap();
+        assert_eq!(config.replace.as_deref(), Some("$1"));
+        assert_eq!(config.paths, vec![PathBuf::from("file")]);
+
+        let config = parse_args(vec!["grope", "--replace=", "a"]).unwrap();
+        assert_eq!(config.replace.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_write_options() {
+        let config = parse_args(vec!["grope", "--replace=b", "--dry-run", "a"]).unwrap();
+        assert!(config.rewrites());
+        assert!(!config.write);
+
+        let args = vec!["grope", "--write", "a"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidArgument(_))));
+
+        let flags = [
+            "-v", "-o", "-c", "-l", "-L", "-m1", "--json", "-q", "-z", "--encoding=latin1",
+        ];
+        for flag in flags {
+            let args = vec!["grope", "--replace=b", "--write", flag, "a"];
+            let err = parse_args(args).unwrap_err().to_string();
+            assert!(err.ends_with("cannot be combined with --write or --dry-run"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_search_zip_option() {
+        assert!(parse_args(vec!["grope", "-z", "pattern"]).unwrap().search_zip);
+        assert!(!parse_args(vec!["grope", "pattern"]).unwrap().search_zip);
+    }
+
+    #[test]
+    fn test_encoding_option() {
+        let config = parse_args(vec!["grope", "--encoding", "Latin1", "pattern"]).unwrap();
+        assert_eq!(config.encoding, Some(encoding_rs::WINDOWS_1252));
+
+        let config = parse_args(vec!["grope", "--encoding=auto", "pattern"]).unwrap();
+        assert_eq!(config.encoding, None);
+
+        let args = vec!["grope", "--encoding=klingon", "pattern"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[test]
+    fn test_word_and_line_options() {
+        let config = parse_args(vec!["grope", "-w", "--line-regexp", "pattern"]).unwrap();
+        assert!(config.word_regexp);
+        assert!(config.line_regexp);
+    }
+
+    #[test]
+    fn test_multiline_options() {
+        let config = parse_args(vec!["grope", "-U", "--multiline-dotall", "a.b"]).unwrap();
+        assert!(config.multiline);
+        assert!(config.multiline_dotall);
+
+        let args = vec!["grope", "-U", "--replace=b", "--write", "a"];
+        assert!(matches!(parse_args(args), Err(GropeError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_builder() {
+        let config = Config::builder()
+            .pattern("a")
+            .pattern("b")
+            .fixed_strings(true)
+            .case_sensitive(false)
+            .context(2)
+            .after_context(1)
+            .build()
+            .unwrap();
+        assert_eq!(config.patterns, vec!["a", "b"]);
+        assert_eq!(config.syntax, PatternSyntax::Fixed);
+        assert!(!config.case_sensitive);
+        assert_eq!((config.before_context, config.after_context), (2, 1));
+        assert_eq!(config.color, ColorChoice::Never);
+
+        assert!(matches!(Config::builder().build(), Err(GropeError::NoPattern)));
+    }
+
+    #[test]
+    fn test_config_file_arguments() {
+        let file = ConfigFile::parse(PathBuf::from("config"), "# defaults\n-n\n-A\n1\n");
+        let config = parse_args_with_config(Some(&file), vec!["grope", "-A2", "p"]).unwrap();
+        assert!(config.line_numbers);
+        assert_eq!(config.after_context, 2);
+        assert_eq!(config.patterns, vec!["p"]);
+
+        let file = ConfigFile::parse(PathBuf::from("config"), "-n\n\n--colour=sometimes\n");
+        let err = parse_args_with_config(Some(&file), vec!["grope", "p"]).unwrap_err();
+        assert!(matches!(err, GropeError::ConfigFile(_, 3, _)));
+        assert_eq!(err.to_string(), "config:3: Invalid value for --colour: sometimes");
+
+        // Errors on the command line or about the whole invocation are not the file's.
+        let err = parse_args_with_config(Some(&file), vec!["grope", "--bogus"]).unwrap_err();
+        assert!(matches!(err, GropeError::ConfigFile(_, 3, _)));
+        let file = ConfigFile::parse(PathBuf::from("config"), "-n\n");
+        let err = parse_args_with_config(Some(&file), vec!["grope", "--bogus"]).unwrap_err();
+        assert!(matches!(err, GropeError::InvalidArgument(_)));
+        let err = parse_args_with_config(Some(&file), vec!["grope"]).unwrap_err();
+        assert!(matches!(err, GropeError::NoPattern));
+    }
+
     #[test]
     fn test_no_pattern_error() {
         let args = vec!["grope"];
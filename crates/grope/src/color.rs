@@ -0,0 +1,103 @@
+use std::env;
+use std::io::IsTerminal;
+use crate::cli::ColorChoice;
+
+/// SGR sequences used to highlight output, overridable through `GREP_COLORS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub matched: String,
+    pub filename: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+}
+
+impl Palette {
+    /// Returns the palette to print with, or `None` when output should stay plain.
+    ///
+    /// `auto` only colors a terminal, and `NO_COLOR` turns it off (https://no-color.org).
+    pub fn for_choice(choice: ColorChoice) -> Option<Self> {
+        let enabled = match choice {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        };
+        if !enabled {
+            return None;
+        }
+
+        let mut palette = Self::default();
+        if let Ok(spec) = env::var("GREP_COLORS") {
+            palette.apply(&spec);
+        }
+        Some(palette)
+    }
+
+    /// Applies a GNU-style `GREP_COLORS` spec such as `ms=01;32:fn=34:ln=33`.
+    ///
+    /// Unknown capabilities and the boolean ones (`rv`, `ne`) are ignored.
+    pub fn apply(&mut self, spec: &str) {
+        for capability in spec.split(':') {
+            let Some((name, value)) = capability.split_once('=') else {
+                continue;
+            };
+            let value = value.to_string();
+            match name {
+                "mt" | "ms" => self.matched = value,
+                "fn" => self.filename = value,
+                "ln" => self.line_number = value,
+                "bn" => self.byte_offset = value,
+                "se" => self.separator = value,
+                _ => {}
+            }
+        }
+    }
+
+    /// Wraps `text` in the given SGR sequence, leaving it untouched if the sequence is empty.
+    pub fn paint(sgr: &str, text: &str) -> String {
+        if sgr.is_empty() || text.is_empty() {
+            text.to_string()
+        } else {
+            format!("\x1b[{}m\x1b[K{}\x1b[m\x1b[K", sgr, text)
+        }
+    }
+}
+
+impl Default for Palette {
+    /// GNU grep's default colors.
+    fn default() -> Self {
+        Self {
+            matched: "01;31".to_string(),
+            filename: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_grep_colors() {
+        let mut palette = Palette::default();
+        palette.apply("ms=01;32:fn=:ln=33:rv:xx=1");
+
+        assert_eq!(palette.matched, "01;32");
+        assert_eq!(palette.filename, "");
+        assert_eq!(palette.line_number, "33");
+        assert_eq!(palette.separator, "36");
+    }
+
+    #[test]
+    fn test_paint() {
+        assert_eq!(Palette::paint("01;31", "hit"), "\x1b[01;31m\x1b[Khit\x1b[m\x1b[K");
+        assert_eq!(Palette::paint("", "hit"), "hit");
+        assert_eq!(Palette::for_choice(ColorChoice::Never), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::cli;
+use crate::error::GropeError;
+
+/// Arguments read from a config file, which `cli::parse_args_with_config` places before the
+/// command line's so that the command line can override them.
+///
+/// The file holds one argument per line, with no quoting: `--exclude=*.min.js` and
+/// `-A` then `2` on the next line both work. Blank lines and lines starting with `#` are
+/// ignored.
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    /// Each argument with its 1-based line number.
+    args: Vec<(usize, String)>,
+}
+
+impl ConfigFile {
+    /// Reads the config file unless `args`, the command line, has the `--no-config` flag.
+    ///
+    /// `GROPE_CONFIG_PATH` names the file and must exist if set; otherwise the file is
+    /// `$XDG_CONFIG_HOME/grope/config`, or `~/.config/grope/config`, and is optional.
+    pub fn load<S: AsRef<str>>(args: &[S]) -> Result<Option<Self>, GropeError> {
+        if cli::has_no_config(args.get(1..).unwrap_or_default()) {
+            return Ok(None);
+        }
+        let Some((path, required)) = locate(|name| env::var_os(name)) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(Self::parse(path, &content))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(None),
+            Err(e) => Err(GropeError::File(path, e)),
+        }
+    }
+
+    pub fn parse(path: PathBuf, content: &str) -> Self {
+        let args = content
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line_number, line)| (line_number, line.to_string()))
+            .collect();
+        Self { path, args }
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.args.iter().map(|(_, arg)| arg.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Points `err`, raised while parsing the argument at `index`, at its line in the file.
+    pub fn error_at(&self, index: usize, err: GropeError) -> GropeError {
+        let line_number = self.args[index].0;
+        GropeError::ConfigFile(self.path.clone(), line_number, Box::new(err))
+    }
+}
+
+/// Finds the config file from the environment, and whether it was named explicitly.
+fn locate<F>(var: F) -> Option<(PathBuf, bool)>
+where
+    F: Fn(&str) -> Option<OsString>,
+{
+    let set = |name| var(name).filter(|value| !value.is_empty());
+    if let Some(path) = set("GROPE_CONFIG_PATH") {
+        return Some((PathBuf::from(path), true));
+    }
+    let config_home = match set("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(set("HOME")?).join(".config"),
+    };
+    Some((config_home.join("grope").join("config"), false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_skips_comments_and_blank_lines() {
+        let content = "# team defaults\n-n\n\n  --smart-case  \n-A\n2\r\n";
+        let file = ConfigFile::parse(PathBuf::from("config"), content);
+
+        assert_eq!(file.args().collect::<Vec<_>>(), vec!["-n", "--smart-case", "-A", "2"]);
+        let err = file.error_at(3, GropeError::InvalidArgument("x".to_string()));
+        assert_eq!(err.to_string(), "config:6: Invalid argument: x");
+    }
+
+    #[test]
+    fn test_locate() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.into())
+            }
+        };
+
+        let vars = &[("GROPE_CONFIG_PATH", "/etc/grope"), ("XDG_CONFIG_HOME", "/xdg")];
+        assert_eq!(locate(env(vars)), Some((PathBuf::from("/etc/grope"), true)));
+
+        let vars = &[("GROPE_CONFIG_PATH", ""), ("XDG_CONFIG_HOME", "/xdg")];
+        assert_eq!(locate(env(vars)), Some((PathBuf::from("/xdg/grope/config"), false)));
+
+        let vars = &[("HOME", "/home/me")];
+        let expected = PathBuf::from("/home/me/.config/grope/config");
+        assert_eq!(locate(env(vars)), Some((expected, false)));
+
+        assert_eq!(locate(env(&[])), None);
+    }
+
+    #[test]
+    fn test_no_config_skips_the_file() {
+        assert!(ConfigFile::load(&["grope", "--no-config", "pattern"]).unwrap().is_none());
+        assert!(ConfigFile::load(&["grope", "pattern", "-A1", "--no-config"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_no_config_as_a_value_is_not_the_flag() {
+        assert!(!cli::has_no_config(&["-e", "--no-config", "file"]));
+        assert!(!cli::has_no_config(&["--regexp", "--no-config"]));
+        assert!(!cli::has_no_config(&["-A", "--no-config"]));
+        assert!(cli::has_no_config(&["-e--no-config", "--no-config"]));
+    }
+}
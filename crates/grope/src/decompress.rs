@@ -0,0 +1,120 @@
+use std::io::{self, BufRead, BufReader};
+use crate::matcher::BUFFER_SIZE;
+
+/// Compression formats that `-z` searches inside, recognised by their magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+    Lz4,
+}
+
+impl Format {
+    /// Identifies the format from the first bytes of a file, whatever its extension.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        const MAGIC: &[(&[u8], Format)] = &[
+            (b"\x1f\x8b", Format::Gzip),
+            (b"\xfd7zXZ\x00", Format::Xz),
+            (b"\x28\xb5\x2f\xfd", Format::Zstd),
+            (b"\x04\x22\x4d\x18", Format::Lz4),
+        ];
+        if is_bzip2(header) {
+            return Some(Format::Bzip2);
+        }
+        MAGIC
+            .iter()
+            .find(|(magic, _)| header.starts_with(magic))
+            .map(|&(_, format)| format)
+    }
+}
+
+/// bzip2's `BZh` is plain text, so the block size digit and the magic of the first block,
+/// or of the end of an empty stream, must follow it.
+fn is_bzip2(header: &[u8]) -> bool {
+    const BLOCK_MAGIC: &[u8] = b"\x31\x41\x59\x26\x53\x59";
+    const END_MAGIC: &[u8] = b"\x17\x72\x45\x38\x50\x90";
+    match header {
+        [b'B', b'Z', b'h', b'1'..=b'9', rest @ ..] => {
+            rest.starts_with(BLOCK_MAGIC) || rest.starts_with(END_MAGIC)
+        }
+        _ => false,
+    }
+}
+
+/// Wraps `reader` in a streaming decoder if it starts with a known magic number, and
+/// returns it unchanged otherwise.
+///
+/// Concatenated members, as produced by appending to rotated logs, are all decoded.
+/// Corrupt input surfaces as an error from the returned reader, for that file alone.
+pub fn decoder<'a>(mut reader: Box<dyn BufRead + 'a>) -> io::Result<Box<dyn BufRead + 'a>> {
+    let Some(format) = Format::detect(reader.fill_buf()?) else {
+        return Ok(reader);
+    };
+    Ok(match format {
+        Format::Gzip => buffered(flate2::bufread::MultiGzDecoder::new(reader)),
+        Format::Xz => buffered(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        Format::Bzip2 => buffered(bzip2::bufread::MultiBzDecoder::new(reader)),
+        Format::Zstd => buffered(zstd::stream::read::Decoder::with_buffer(reader)?),
+        Format::Lz4 => buffered(lz4_flex::frame::FrameDecoder::new(reader)),
+    })
+}
+
+fn buffered<'a>(decoder: impl io::Read + 'a) -> Box<dyn BufRead + 'a> {
+    Box::new(BufReader::with_capacity(BUFFER_SIZE, decoder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const TEXT: &[u8] = b"first line\nsecond line\n";
+
+    fn decode(compressed: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        decoder(Box::new(io::Cursor::new(compressed)))?.read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn test_decode_every_format() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(TEXT).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 1);
+        xz.write_all(TEXT).unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        bzip2.write_all(TEXT).unwrap();
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        lz4.write_all(TEXT).unwrap();
+
+        let compressed = [
+            (Format::Gzip, gzip.finish().unwrap()),
+            (Format::Xz, xz.finish().unwrap()),
+            (Format::Bzip2, bzip2.finish().unwrap()),
+            (Format::Zstd, zstd::encode_all(TEXT, 1).unwrap()),
+            (Format::Lz4, lz4.finish().unwrap()),
+        ];
+        for (format, bytes) in compressed {
+            assert_eq!(Format::detect(&bytes), Some(format));
+            assert_eq!(decode(bytes).unwrap(), TEXT);
+        }
+    }
+
+    #[test]
+    fn test_plain_and_corrupt_input() {
+        assert_eq!(Format::detect(TEXT), None);
+        assert_eq!(decode(TEXT.to_vec()).unwrap(), TEXT);
+        for text in [&b"BZh is a prefix\n"[..], b"BZh9 text\n", b"BZh"] {
+            assert_eq!(Format::detect(text), None);
+            assert_eq!(decode(text.to_vec()).unwrap(), text);
+        }
+        let empty = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        assert_eq!(Format::detect(&empty.finish().unwrap()), Some(Format::Bzip2));
+
+        let mut truncated = zstd::encode_all(TEXT, 1).unwrap();
+        truncated.truncate(8);
+        assert!(decode(truncated).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use std::io::{self, BufRead};
+use encoding_rs::{CoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Works out how a file is encoded from its first bytes: a byte order mark wins over
+/// `--encoding`. Returns the encoding, if any, and the length of the BOM to skip.
+pub fn detect(
+    header: &[u8],
+    configured: Option<&'static Encoding>,
+) -> (Option<&'static Encoding>, usize) {
+    match Encoding::for_bom(header) {
+        Some((encoding, bom_length)) => (Some(encoding), bom_length),
+        None => (configured, 0),
+    }
+}
+
+/// Whether `encoding` uses two bytes per code unit, so its text is full of NUL bytes.
+pub fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+/// Finds where each character of `raw`, a line in `encoding`, came from once transcoded.
+///
+/// Each checkpoint pairs a position in the UTF-8 line with the offset in `raw` of the
+/// bytes it was decoded from. Bytes between two checkpoints map one to one, so an empty
+/// list means the whole line does.
+///
+/// The line is decoded again a byte or an ASCII run at a time. A character starts where
+/// the input the decoder had not turned into text did. When one step produces several
+/// characters, as when an invalid sequence is followed by bytes decoded on their own, the
+/// ones after the first take the last bytes read.
+pub fn origins(encoding: &'static Encoding, raw: &[u8]) -> Vec<(usize, usize)> {
+    let mut origins = Vec::new();
+    if encoding.is_ascii_compatible() && raw.is_ascii() {
+        return origins;
+    }
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    // Room for the whole line, since a byte can release text held back for earlier ones.
+    let mut output = vec![0; decoder.max_utf8_buffer_length(raw.len()).unwrap_or(usize::MAX)];
+    let mut decoded = 0;
+    let mut pending = 0;
+    let mut read = 0;
+    loop {
+        let last = read == raw.len();
+        // With nothing held back, a run of ASCII decodes one byte per character.
+        let ascii = if pending == read && encoding.is_ascii_compatible() {
+            raw[read..].iter().take_while(|byte| byte.is_ascii()).count()
+        } else {
+            0
+        };
+        let input = &raw[read..(read + ascii.max(1)).min(raw.len())];
+        let (_, _, written, _) = decoder.decode_to_utf8(input, &mut output, last);
+        let consumed = read + input.len();
+        let starts = (0..written).filter(|&i| output[i] & 0xc0 != 0x80);
+        let characters = starts.clone().count();
+        for (i, start) in starts.enumerate() {
+            let origin = if i == 0 {
+                pending
+            } else {
+                consumed.saturating_sub(characters - i).max(pending)
+            };
+            let position = decoded + start;
+            if original_position(&origins, position) != origin {
+                origins.push((position, origin));
+            }
+        }
+        if written > 0 {
+            pending = consumed;
+        }
+        decoded += written;
+        if last {
+            return origins;
+        }
+        read = consumed;
+    }
+}
+
+/// The offset in the original line of `position` in its transcoded form, given the
+/// checkpoints from `origins`.
+pub fn original_position(origins: &[(usize, usize)], position: usize) -> usize {
+    match origins.partition_point(|&(decoded, _)| decoded <= position) {
+        0 => position,
+        next => {
+            let (decoded, original) = origins[next - 1];
+            original + position - decoded
+        }
+    }
+}
+
+/// Reads lines in another encoding and transcodes them to UTF-8 for matching and printing.
+///
+/// Lines are split in the original encoding, so the byte count returned for each line
+/// is the one in the file. Sequences that cannot be decoded become U+FFFD.
+pub struct Transcoder {
+    encoding: &'static Encoding,
+    /// The byte that ends a line: `\n`, or NUL for `--null-data`.
+    terminator: u8,
+    raw: Vec<u8>,
+}
+
+impl Transcoder {
+    /// Returns `None` for UTF-8, which is searched as raw bytes.
+    pub fn new(encoding: &'static Encoding, terminator: u8) -> Option<Self> {
+        (encoding != UTF_8).then(|| Self { encoding, terminator, raw: Vec::new() })
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// The last line as it was read, before transcoding and with its terminator.
+    pub fn raw_line(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Like `searcher::read_line`, but `line` receives the UTF-8 form of the next line.
+    pub fn read_line(&mut self, reader: &mut dyn BufRead, line: &mut Vec<u8>) -> io::Result<usize> {
+        self.raw.clear();
+        self.read_raw_line(reader)?;
+
+        let mut decoder = self.encoding.new_decoder_without_bom_handling();
+        let capacity = decoder.max_utf8_buffer_length(self.raw.len()).unwrap_or(usize::MAX);
+        line.clear();
+        line.resize(capacity, 0);
+        let (result, _, written, _) = decoder.decode_to_utf8(&self.raw, line, true);
+        debug_assert_eq!(result, CoderResult::InputEmpty);
+        line.truncate(written);
+
+        strip_terminator(line, self.terminator);
+        Ok(self.raw.len())
+    }
+
+    /// Reads up to and including the next terminator code unit into `self.raw`.
+    fn read_raw_line(&mut self, reader: &mut dyn BufRead) -> io::Result<()> {
+        let terminator = self.terminator;
+        if !is_utf16(self.encoding) {
+            // Every other supported encoding keeps `\n` and NUL out of its multi-byte
+            // sequences.
+            reader.read_until(terminator, &mut self.raw)?;
+            return Ok(());
+        }
+
+        // A 0x0a byte only ends the line if it belongs to a U+000A code unit, and the same
+        // goes for NUL bytes and U+0000.
+        let little_endian = self.encoding == UTF_16LE;
+        loop {
+            let read = reader.read_until(terminator, &mut self.raw)?;
+            if read == 0 || self.raw.last() != Some(&terminator) {
+                return Ok(());
+            }
+            let index = self.raw.len() - 1;
+            if little_endian && index.is_multiple_of(2) {
+                let Some(&next) = reader.fill_buf()?.first() else {
+                    return Ok(());
+                };
+                reader.consume(1);
+                self.raw.push(next);
+                if next == 0 {
+                    return Ok(());
+                }
+            } else if !little_endian && !index.is_multiple_of(2) && self.raw[index - 1] == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Removes the line terminator from the end of `line`, and a carriage return before a
+/// `\n` so that CRLF files search like LF ones.
+pub fn strip_terminator(line: &mut Vec<u8>, terminator: u8) {
+    if line.last() == Some(&terminator) {
+        line.pop();
+        if terminator == b'\n' && line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    fn read_all(encoding: &'static Encoding, input: &[u8]) -> Vec<(String, usize)> {
+        read_records(encoding, input, b'\n')
+    }
+
+    fn read_records(
+        encoding: &'static Encoding,
+        input: &[u8],
+        terminator: u8,
+    ) -> Vec<(String, usize)> {
+        let mut transcoder = Transcoder::new(encoding, terminator).unwrap();
+        let mut reader = input;
+        let mut line = Vec::new();
+        let mut lines = Vec::new();
+        loop {
+            let read = transcoder.read_line(&mut reader, &mut line).unwrap();
+            if read == 0 {
+                return lines;
+            }
+            lines.push((String::from_utf8(line.clone()).unwrap(), read));
+        }
+    }
+
+    #[test]
+    fn test_detect_bom() {
+        assert_eq!(detect(b"\xff\xfea\x00", None), (Some(UTF_16LE), 2));
+        assert_eq!(detect(b"\xfe\xff\x00a", Some(WINDOWS_1252)), (Some(UTF_16BE), 2));
+        assert_eq!(detect(b"\xef\xbb\xbfa", None), (Some(UTF_8), 3));
+        assert_eq!(detect(b"abc", Some(WINDOWS_1252)), (Some(WINDOWS_1252), 0));
+        assert!(Transcoder::new(UTF_8, b'\n').is_none());
+    }
+
+    #[test]
+    fn test_utf16_lines() {
+        // U+0A0A contains two 0x0a bytes but is not a newline.
+        let text: Vec<u16> = "a\u{a0a}b\r\ncaf\u{e9}".encode_utf16().collect();
+        let little: Vec<u8> = text.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+        let big: Vec<u8> = text.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+
+        let expected = vec![("a\u{a0a}b".to_string(), 10), ("caf\u{e9}".to_string(), 8)];
+        assert_eq!(read_all(UTF_16LE, &little), expected);
+        assert_eq!(read_all(UTF_16BE, &big), expected);
+
+        // U+0100 contains a NUL byte but is not a NUL character.
+        let text: Vec<u16> = "\u{100}a\0b\n".encode_utf16().collect();
+        let little: Vec<u8> = text.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+        let big: Vec<u8> = text.iter().flat_map(|unit| unit.to_be_bytes()).collect();
+        let expected = vec![("\u{100}a".to_string(), 6), ("b\n".to_string(), 4)];
+        assert_eq!(read_records(UTF_16LE, &little, 0), expected);
+        assert_eq!(read_records(UTF_16BE, &big, 0), expected);
+    }
+
+    #[test]
+    fn test_single_and_multi_byte_encodings() {
+        assert_eq!(
+            read_all(WINDOWS_1252, b"caf\xe9 \x80\nx"),
+            vec![("caf\u{e9} \u{20ac}".to_string(), 7), ("x".to_string(), 1)]
+        );
+        assert_eq!(read_all(SHIFT_JIS, b"\x93\xfa\x96\x7b\n"), vec![("日本".to_string(), 5)]);
+    }
+
+    #[test]
+    fn test_origins() {
+        let starts = |encoding: &'static Encoding, input: &[u8]| {
+            let (line, _) = encoding.decode_without_bom_handling(input);
+            let origins = origins(encoding, input);
+            let starts = line.char_indices().map(|(i, _)| original_position(&origins, i));
+            starts.collect::<Vec<_>>()
+        };
+        assert_eq!(starts(WINDOWS_1252, b"abc"), vec![0, 1, 2]);
+        assert_eq!(starts(WINDOWS_1252, b"\xe9t\xe9"), vec![0, 1, 2]);
+        assert_eq!(starts(SHIFT_JIS, b"a\x93\xfa\x96\x7bb"), vec![0, 1, 3, 5]);
+        // The invalid lead byte becomes U+FFFD and the space after it is decoded on its own.
+        assert_eq!(starts(SHIFT_JIS, b"\x81 hit"), vec![0, 1, 2, 3, 4]);
+        assert_eq!(starts(UTF_16LE, b"a\x00\x3d\xd8\x00\xdeb\x00"), vec![0, 2, 6]);
+    }
+}
@@ -1,11 +1,20 @@
 use std::fmt;
+use std::path::PathBuf;
 pub use std::error::Error;
 
 #[derive(Debug)]
 pub enum GropeError {
     NoPattern,
     InvalidArgument(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    InvalidPattern(String),
+    InvalidGlob(globset::Error),
     Io(std::io::Error),
+    /// A file or directory that could not be searched; the rest of the run carries on.
+    File(PathBuf, std::io::Error),
+    /// An error in the argument on the given line of a config file.
+    ConfigFile(PathBuf, usize, Box<GropeError>),
 }
 
 impl fmt::Display for GropeError {
@@ -13,7 +22,17 @@ impl fmt::Display for GropeError {
         match self {
             GropeError::NoPattern => write!(f, "No pattern provided"),
             GropeError::InvalidArgument(arg) => write!(f, "Invalid argument: {}", arg),
+            GropeError::MissingValue(flag) => write!(f, "Missing value for {}", flag),
+            GropeError::InvalidValue(flag, value) => {
+                write!(f, "Invalid value for {}: {}", flag, value)
+            }
+            GropeError::InvalidPattern(err) => write!(f, "Invalid pattern: {}", err),
+            GropeError::InvalidGlob(err) => write!(f, "Invalid glob: {}", err),
             GropeError::Io(err) => write!(f, "IO error: {}", err),
+            GropeError::File(path, err) => write!(f, "{}: {}", path.display(), err),
+            GropeError::ConfigFile(path, line_number, err) => {
+                write!(f, "{}:{}: {}", path.display(), line_number, err)
+            }
         }
     }
 }
@@ -25,3 +44,15 @@ impl From<std::io::Error> for GropeError {
         GropeError::Io(err)
     }
 }
+
+impl From<regex::Error> for GropeError {
+    fn from(err: regex::Error) -> Self {
+        GropeError::InvalidPattern(err.to_string())
+    }
+}
+
+impl From<globset::Error> for GropeError {
+    fn from(err: globset::Error) -> Self {
+        GropeError::InvalidGlob(err)
+    }
+}
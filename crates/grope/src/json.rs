@@ -0,0 +1,146 @@
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use crate::stats::{FileStats, Stats};
+
+/// Builds the events of `--json` output, one JSON object per line.
+///
+/// Text that is not valid UTF-8 is sent as `{"bytes": <base64>}` instead of `{"text": ...}`.
+pub fn begin(path: &Path) -> Value {
+    json!({
+        "type": "begin",
+        "data": { "path": path_data(path) },
+    })
+}
+
+/// A selected (`"match"`) or `"context"` line, with the byte spans of its submatches.
+///
+/// With `--replace`, each submatch also carries its `"replacement"`.
+pub fn line(
+    kind: &str,
+    path: &Path,
+    line_number: usize,
+    offset: u64,
+    line: &[u8],
+    matches: &[Range<usize>],
+    replacements: Option<&[Vec<u8>]>,
+) -> Value {
+    let submatches: Vec<Value> = matches
+        .iter()
+        .enumerate()
+        .map(|(i, range)| {
+            let mut submatch = json!({
+                "match": data(&line[range.clone()]),
+                "start": range.start,
+                "end": range.end,
+            });
+            if let Some(replacements) = replacements {
+                submatch["replacement"] = data(&replacements[i]);
+            }
+            submatch
+        })
+        .collect();
+
+    json!({
+        "type": kind,
+        "data": {
+            "path": path_data(path),
+            "lines": data(line),
+            "line_number": line_number,
+            "absolute_offset": offset,
+            "submatches": submatches,
+        },
+    })
+}
+
+pub fn end(path: &Path, binary: bool, stats: &FileStats) -> Value {
+    json!({
+        "type": "end",
+        "data": {
+            "path": path_data(path),
+            "binary": binary,
+            "stats": {
+                "matched_lines": stats.matched_lines,
+                "matches": stats.matches,
+                "bytes_searched": stats.bytes_searched,
+            },
+        },
+    })
+}
+
+pub fn summary(stats: &Stats, elapsed: Duration) -> Value {
+    json!({
+        "type": "summary",
+        "data": {
+            "elapsed_total": {
+                "secs": elapsed.as_secs(),
+                "nanos": elapsed.subsec_nanos(),
+                "human": format!("{:.6}s", elapsed.as_secs_f64()),
+            },
+            "stats": {
+                "searches": stats.searches,
+                "searches_with_match": stats.searches_with_match,
+                "matched_lines": stats.matched_lines,
+                "matches": stats.matches,
+                "bytes_searched": stats.bytes_searched,
+            },
+        },
+    })
+}
+
+fn data(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => json!({ "text": text }),
+        Err(_) => json!({ "bytes": BASE64.encode(bytes) }),
+    }
+}
+
+#[cfg(unix)]
+fn path_data(path: &Path) -> Value {
+    use std::os::unix::ffi::OsStrExt;
+    data(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_data(path: &Path) -> Value {
+    json!({ "text": path.to_string_lossy() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_event() {
+        let event = line("match", Path::new("a.txt"), 3, 42, b"say hi hi", &[4..6, 7..9], None);
+
+        assert_eq!(
+            event.to_string(),
+            concat!(
+                r#"{"type":"match","data":{"path":{"text":"a.txt"},"lines":{"text":"say hi hi"},"#,
+                r#""line_number":3,"absolute_offset":42,"submatches":["#,
+                r#"{"match":{"text":"hi"},"start":4,"end":6},"#,
+                r#"{"match":{"text":"hi"},"start":7,"end":9}]}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_replacement() {
+        let replacements = [b"yo".to_vec(), b"".to_vec()];
+        let matches = [0..2, 3..5];
+        let event = line("match", Path::new("a"), 1, 0, b"hi hi", &matches, Some(&replacements));
+
+        assert_eq!(event["data"]["submatches"][0]["replacement"], json!({ "text": "yo" }));
+        assert_eq!(event["data"]["submatches"][1]["replacement"], json!({ "text": "" }));
+    }
+
+    #[test]
+    fn test_non_utf8_is_base64() {
+        assert_eq!(data(b"caf\xe9"), json!({ "bytes": "Y2Fm6Q==" }));
+        assert_eq!(data("café".as_bytes()), json!({ "text": "café" }));
+    }
+}
@@ -0,0 +1,31 @@
+#![allow(unused_qualifications)]
+
+//! grope searches files for lines matching a pattern, like grep.
+//!
+//! The command-line tool is a thin layer over this library: a [`Searcher`] reads any
+//! [`std::io::Read`] and reports each selected and context line to a [`Sink`]. The
+//! [`Printer`] is the sink that produces grope's own output.
+
+pub mod cli;
+mod color;
+pub mod config_file;
+mod decompress;
+mod encoding;
+pub mod error;
+mod json;
+pub mod matcher;
+pub mod pool;
+pub mod printer;
+mod rewriter;
+pub mod searcher;
+pub mod sink;
+pub mod stats;
+pub mod types;
+pub mod walker;
+
+pub use cli::{Config, ConfigBuilder};
+pub use error::GropeError;
+pub use printer::Printer;
+pub use searcher::Searcher;
+pub use sink::{Sink, SinkContext, SinkMatch};
+pub use stats::FileStats;
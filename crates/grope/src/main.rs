@@ -1,30 +1,59 @@
-#![allow(unused_qualifications)]
-
-mod cli;
-mod matcher;
-mod printer;
-mod error;
-
+use grope::cli;
+use grope::config_file::ConfigFile;
+use grope::error::GropeError;
+use grope::pool::Pool;
+use grope::searcher::Searcher;
+use grope::types::FileTypes;
+use grope::walker::Walker;
+use std::io;
 use std::process;
 
+/// Exit statuses, following grep: a line was selected, nothing was, or something failed.
+const EXIT_MATCH: i32 = 0;
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
 fn main() {
     match run() {
-        Ok(_) => process::exit(0),
+        Ok(status) => process::exit(status),
+        // The reader went away, as with `grope ... | head -1`, which is not a failure.
+        Err(e) if is_broken_pipe(&*e) => process::exit(EXIT_MATCH),
         Err(e) => {
             eprintln!("Error: {}", e);
-            process::exit(1);
+            process::exit(EXIT_ERROR);
         }
     }
 }
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let config = cli::parse_args(std::env::args().collect::<Vec<_>>())?;
-    let matcher = matcher::Matcher::new(&config);
-    let printer = printer::Printer::new(&config);
+fn is_broken_pipe(error: &(dyn std::error::Error + 'static)) -> bool {
+    match error.downcast_ref::<GropeError>() {
+        Some(GropeError::Io(e)) => e.kind() == io::ErrorKind::BrokenPipe,
+        _ => error
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe),
+    }
+}
 
-    for path in &config.paths {
-        matcher.search_file(path, &printer)?;
+fn run() -> Result<i32, Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let config_file = ConfigFile::load(&args)?;
+    let config = cli::parse_args_with_config(config_file.as_ref(), args)?;
+    if config.type_list {
+        FileTypes::new(&config.type_add)?.write_list(&mut io::stdout().lock())?;
+        return Ok(EXIT_MATCH);
     }
+    let searcher = Searcher::new(&config)?;
+    let walker = Walker::new(&config)?;
+
+    let stats = Pool::new(&config, &searcher, &walker).run()?;
 
-    Ok(())
+    // Like grep, -q reports a match even if another file could not be read.
+    let matched = stats.matched_lines > 0;
+    Ok(if stats.errors > 0 && !(config.quiet && matched) {
+        EXIT_ERROR
+    } else if matched {
+        EXIT_MATCH
+    } else {
+        EXIT_NO_MATCH
+    })
 }
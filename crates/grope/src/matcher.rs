@@ -1,55 +1,396 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::Path;
-use crate::cli::Config;
-use crate::printer::Printer;
+use std::cmp::Reverse;
+use std::ops::Range;
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::ast::{Ast, ClassSetItem};
+use crate::cli::{Config, PatternSyntax};
+use crate::error::GropeError;
+
+/// Read buffer size; large reads keep syscalls rare on big files.
+pub const BUFFER_SIZE: usize = 256 * 1024;
+
+/// The compiled form of every pattern, searched in a single pass over each line.
+enum Engine {
+    /// All patterns joined into one alternation.
+    Regex(Regex),
+    /// Fixed strings, matched together by an Aho-Corasick automaton.
+    ///
+    /// With `-w` or `-x` the automaton reports overlapping matches, so a hit that is not
+    /// bounded can be retried with a shorter pattern or at a later position.
+    Literals(AhoCorasick),
+}
 
 pub struct Matcher<'a> {
     config: &'a Config,
+    engine: Engine,
 }
 
 impl<'a> Matcher<'a> {
-    pub fn new(config: &'a Config) -> Self {
-        Self { config }
+    /// Compiles the configured patterns once so every line is tested against the same engine.
+    pub fn new(config: &'a Config) -> Result<Self, GropeError> {
+        let ignore_case = ignores_case(config);
+        let engine = if use_literals(config, ignore_case) {
+            let match_kind = if config.word_regexp || config.line_regexp {
+                MatchKind::Standard
+            } else {
+                MatchKind::LeftmostLongest
+            };
+            let automaton = AhoCorasick::builder()
+                .match_kind(match_kind)
+                .ascii_case_insensitive(ignore_case)
+                .build(&config.patterns)
+                .map_err(|e| GropeError::InvalidPattern(e.to_string()))?;
+            Engine::Literals(automaton)
+        } else {
+            Engine::Regex(build_regex(config, ignore_case)?)
+        };
+
+        Ok(Self { config, engine })
     }
 
-    pub fn search_file(&self, path: &Path, printer: &Printer) -> io::Result<()> {
-        let reader: Box<dyn BufRead> = if path.to_str() == Some("-") {
-            Box::new(BufReader::new(io::stdin()))
-        } else {
-            Box::new(BufReader::new(File::open(path)?))
+    pub fn is_match(&self, line: &[u8]) -> bool {
+        match &self.engine {
+            Engine::Regex(regex) => regex.is_match(line),
+            Engine::Literals(automaton) if self.bounded() => automaton
+                .find_overlapping_iter(line)
+                .any(|m| self.is_bounded(line, &m.range())),
+            Engine::Literals(automaton) => automaton.is_match(line),
+        }
+    }
+
+    /// Returns the byte ranges of every non-empty match in `line`.
+    pub fn find_matches(&self, line: &[u8]) -> Vec<Range<usize>> {
+        let mut matches: Vec<Range<usize>> = match &self.engine {
+            Engine::Regex(regex) => regex.find_iter(line).map(|m| m.range()).collect(),
+            Engine::Literals(automaton) if self.bounded() => self.bounded_literals(automaton, line),
+            Engine::Literals(automaton) => automaton.find_iter(line).map(|m| m.range()).collect(),
         };
+        matches.retain(|range| !range.is_empty());
+        matches
+    }
 
-        let mut matches = 0;
-        for (i, line) in reader.lines().enumerate() {
-            let line = line?;
-            let matches_pattern = if self.config.case_sensitive {
-                line.contains(&self.config.pattern)
-            } else {
-                line.to_lowercase().contains(&self.config.pattern.to_lowercase())
-            };
+    /// Returns the first match in `haystack` at or after `start`, even an empty one.
+    ///
+    /// `-U` uses this to search text that spans many lines.
+    pub fn find_at(&self, haystack: &[u8], start: usize) -> Option<Range<usize>> {
+        let Engine::Regex(regex) = &self.engine else {
+            unreachable!("-U always searches with the regex engine");
+        };
+        regex.find_at(haystack, start).map(|m| m.range())
+    }
 
-            if matches_pattern != self.config.invert_match {
-                matches += 1;
-                if !self.config.count_only {
-                    printer.print_match(path, i + 1, &line)?;
+    /// Expands the `--replace` template for each match in `line`, or returns `None` when
+    /// no replacement was asked for.
+    pub fn replacements(&self, line: &[u8], matches: &[Range<usize>]) -> Option<Vec<Vec<u8>>> {
+        let template = self.config.replace.as_ref()?;
+        let Engine::Regex(regex) = &self.engine else {
+            unreachable!("--replace always searches with the regex engine");
+        };
+        let replacements = matches
+            .iter()
+            .map(|range| {
+                let mut replacement = Vec::new();
+                // Searching from the match start finds that same match again, with captures.
+                if let Some(captures) = regex.captures_at(line, range.start) {
+                    captures.expand(template.as_bytes(), &mut replacement);
                 }
+                replacement
+            })
+            .collect();
+        Some(replacements)
+    }
+
+    /// Whether `-w` or `-x` restricts where a match may start and end.
+    fn bounded(&self) -> bool {
+        self.config.word_regexp || self.config.line_regexp
+    }
+
+    fn is_bounded(&self, line: &[u8], range: &Range<usize>) -> bool {
+        if self.config.line_regexp {
+            range.start == 0 && range.end == line.len()
+        } else {
+            !is_word_char(char_before(line, range.start))
+                && !is_word_char(char_after(line, range.end))
+        }
+    }
+
+    /// Picks the leftmost-longest bounded matches that do not overlap among every hit.
+    fn bounded_literals(&self, automaton: &AhoCorasick, line: &[u8]) -> Vec<Range<usize>> {
+        let mut candidates: Vec<Range<usize>> = automaton
+            .find_overlapping_iter(line)
+            .map(|m| m.range())
+            .filter(|range| self.is_bounded(line, range))
+            .collect();
+        candidates.sort_by_key(|range| (range.start, Reverse(range.end)));
+
+        let mut matches: Vec<Range<usize>> = Vec::new();
+        for range in candidates {
+            if matches.last().is_none_or(|last| range.start >= last.end) {
+                matches.push(range);
             }
         }
+        matches
+    }
+}
 
-        if self.config.count_only {
-            printer.print_count(path, matches)?;
+/// Whether matching ignores case: always with `-i`, and with `-S` only when no pattern
+/// contains an uppercase letter.
+fn ignores_case(config: &Config) -> bool {
+    if config.smart_case {
+        !config.patterns.iter().any(|pattern| has_uppercase(pattern, config.syntax))
+    } else {
+        !config.case_sensitive
+    }
+}
+
+/// Whether `pattern` spells out an uppercase letter, ignoring escapes such as `\S` and
+/// class names such as `\p{Lu}` that only look like one.
+fn has_uppercase(pattern: &str, syntax: PatternSyntax) -> bool {
+    let pattern = match syntax {
+        PatternSyntax::Fixed => return pattern.chars().any(char::is_uppercase),
+        PatternSyntax::Extended => pattern.to_string(),
+        PatternSyntax::Basic => basic_to_extended(pattern),
+    };
+    match regex_syntax::ast::parse::Parser::new().parse(&pattern) {
+        Ok(ast) => regex_syntax::ast::visit(&ast, UppercaseLiterals(false)).unwrap_or(false),
+        // The regex build reports the error; until then, any uppercase letter counts.
+        Err(_) => pattern.chars().any(char::is_uppercase),
+    }
+}
+
+/// Looks for uppercase letters among the literals of a pattern, inside classes too.
+struct UppercaseLiterals(bool);
+
+impl regex_syntax::ast::Visitor for UppercaseLiterals {
+    type Output = bool;
+    type Err = ();
+
+    fn finish(self) -> Result<bool, ()> {
+        Ok(self.0)
+    }
+
+    fn visit_pre(&mut self, ast: &Ast) -> Result<(), ()> {
+        if let Ast::Literal(literal) = ast {
+            self.0 |= literal.c.is_uppercase();
         }
+        Ok(())
+    }
 
+    fn visit_class_set_item_pre(&mut self, item: &ClassSetItem) -> Result<(), ()> {
+        match item {
+            ClassSetItem::Literal(literal) => self.0 |= literal.c.is_uppercase(),
+            ClassSetItem::Range(range) => {
+                self.0 |= range.start.c.is_uppercase() || range.end.c.is_uppercase();
+            }
+            _ => {}
+        }
         Ok(())
     }
 }
 
+/// Fixed strings go through Aho-Corasick, unless case folding needs Unicode tables,
+/// `--replace` needs capture groups or `-U` needs `^` and `$` to anchor at every line.
+///
+/// Aho-Corasick only folds ASCII, which is not enough even for ASCII patterns: `k` and `s`
+/// also match the Kelvin sign and the long s under Unicode simple case folding.
+fn use_literals(config: &Config, ignore_case: bool) -> bool {
+    let folds_in_ascii =
+        |pattern: &String| pattern.is_ascii() && !pattern.contains(['k', 'K', 's', 'S']);
+    config.syntax == PatternSyntax::Fixed
+        && config.replace.is_none()
+        && !config.multiline
+        && (!ignore_case || config.patterns.iter().all(folds_in_ascii))
+}
+
+/// Joins every pattern into a single alternation, so a line is scanned only once.
+///
+/// The alternation prefers its first branch that matches, so fixed strings are put longest
+/// first to match what Aho-Corasick's leftmost-longest search finds.
+///
+/// Case-insensitive patterns are compiled with Unicode simple case folding, so lines are
+/// matched as they are, never lowercased.
+fn build_regex(config: &Config, ignore_case: bool) -> Result<Regex, GropeError> {
+    let mut patterns: Vec<&String> = config.patterns.iter().collect();
+    if config.syntax == PatternSyntax::Fixed {
+        patterns.sort_by_key(|pattern| std::cmp::Reverse(pattern.chars().count()));
+    }
+    let patterns: Vec<String> = patterns
+        .into_iter()
+        .map(|pattern| match config.syntax {
+            PatternSyntax::Extended => pattern.clone(),
+            PatternSyntax::Fixed => regex::escape(pattern),
+            PatternSyntax::Basic => basic_to_extended(pattern),
+        })
+        .collect();
+
+    // Check each pattern on its own first: joined together, two broken patterns such
+    // as `a)|(?:b` and `c` can form a valid regex, and errors should name the culprit.
+    if patterns.len() > 1 {
+        for pattern in &patterns {
+            regex_syntax::Parser::new()
+                .parse(pattern)
+                .map_err(|e| GropeError::InvalidPattern(e.to_string()))?;
+        }
+    }
+
+    let joined = match patterns.as_slice() {
+        [pattern] => pattern.clone(),
+        // An empty alternation would match everywhere, but no patterns should match nothing.
+        [] => "[^\\x00-\\x{10FFFF}]".to_string(),
+        _ => {
+            let groups: Vec<String> =
+                patterns.iter().map(|pattern| format!("(?:{})", pattern)).collect();
+            groups.join("|")
+        }
+    };
+    // The half word boundaries only look at the neighbouring character, like GNU grep's
+    // -w, and the regex engine retries shorter matches and later positions by itself.
+    let joined = if config.line_regexp {
+        format!("^(?:{})$", joined)
+    } else if config.word_regexp {
+        format!(r"\b{{start-half}}(?:{})\b{{end-half}}", joined)
+    } else {
+        joined
+    };
+    // Lines are searched without their terminator, so `^` and `$` only need to anchor at
+    // every line for -U, where the searched text spans many of them.
+    Ok(RegexBuilder::new(&joined)
+        .case_insensitive(ignore_case)
+        .multi_line(config.multiline)
+        .line_terminator(config.line_terminator())
+        .dot_matches_new_line(config.multiline && config.multiline_dotall)
+        .build()?)
+}
+
+/// Word characters for `-w`: letters, digits and underscore.
+fn is_word_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Decodes the character that ends at `end`, if it is valid UTF-8.
+fn char_before(line: &[u8], end: usize) -> Option<char> {
+    (end.saturating_sub(4)..end)
+        .find_map(|start| std::str::from_utf8(&line[start..end]).ok())
+        .and_then(|text| text.chars().next_back())
+}
+
+/// Decodes the character that starts at `start`, if it is valid UTF-8.
+fn char_after(line: &[u8], start: usize) -> Option<char> {
+    (start + 1..=line.len().min(start + 4))
+        .find_map(|end| std::str::from_utf8(&line[start..end]).ok())
+        .and_then(|text| text.chars().next())
+}
+
+/// Rewrites a POSIX basic regular expression into the extended syntax understood by `regex`.
+///
+/// In BREs the grouping, alternation and interval operators are only special when escaped,
+/// `*` is literal at the start of an expression, and `^`/`$` only anchor at the ends.
+fn basic_to_extended(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len());
+    let mut at_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let expression_start = at_start;
+        at_start = false;
+
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                i += 1;
+                match next {
+                    '(' | '|' => {
+                        out.push(next);
+                        at_start = true;
+                    }
+                    ')' | '{' | '}' | '+' | '?' => out.push(next),
+                    _ => {
+                        out.push('\\');
+                        out.push(next);
+                    }
+                }
+            }
+            '\\' => out.push_str("\\\\"),
+            '^' if expression_start => {
+                out.push('^');
+                at_start = true;
+            }
+            '$' if at_expression_end(&chars, i + 1) => out.push('$'),
+            '*' if expression_start => out.push_str("\\*"),
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' | '^' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '[' => i = copy_bracket(&chars, i, &mut out),
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+
+    out
+}
+
+fn at_expression_end(chars: &[char], i: usize) -> bool {
+    i == chars.len() || (chars[i] == '\\' && matches!(chars.get(i + 1), Some(')') | Some('|')))
+}
+
+/// Copies a bracket expression starting at `start`, returning the index of its closing `]`.
+///
+/// Backslashes and nested `[` are literal inside POSIX brackets but special to `regex`.
+fn copy_bracket(chars: &[char], start: usize, out: &mut String) -> usize {
+    let mut i = start + 1;
+    let mut class = String::from("[");
+    if chars.get(i) == Some(&'^') {
+        class.push('^');
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        class.push_str("\\]");
+        i += 1;
+    }
+
+    while i < chars.len() {
+        match chars[i] {
+            ']' => {
+                class.push(']');
+                out.push_str(&class);
+                return i;
+            }
+            '[' if matches!(chars.get(i + 1), Some(':') | Some('.') | Some('=')) => {
+                let delimiter = chars[i + 1];
+                let close = (i + 2..chars.len().saturating_sub(1))
+                    .find(|&j| chars[j] == delimiter && chars[j + 1] == ']');
+                match close {
+                    Some(j) => {
+                        class.extend(&chars[i..j + 2]);
+                        i = j + 1;
+                    }
+                    None => class.push_str("\\["),
+                }
+            }
+            c @ ('\\' | '[' | '&' | '~') => {
+                class.push('\\');
+                class.push(c);
+            }
+            c => class.push(c),
+        }
+        i += 1;
+    }
+
+    // Unterminated bracket: hand it to the regex compiler so the error points at it.
+    out.push_str(&class);
+    chars.len() - 1
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::NamedTempFile;
     use std::io::Write;
+    use tempfile::NamedTempFile;
+    use crate::printer::Printer;
+    use crate::searcher::Searcher;
 
     fn create_test_file(content: &str) -> NamedTempFile {
         let mut file = NamedTempFile::new().unwrap();
@@ -57,39 +398,276 @@ mod tests {
         file
     }
 
+    fn config_with(pattern: &str, syntax: PatternSyntax) -> Config {
+        Config {
+            patterns: vec![pattern.to_string()],
+            syntax,
+            ..Config::new()
+        }
+    }
+
     #[test]
     fn test_case_sensitive_search() {
         let config = Config {
-            pattern: "Hello".to_string(),
+            patterns: vec!["Hello".to_string()],
             paths: vec![],
             case_sensitive: true,
-            invert_match: false,
-            line_numbers: false,
             count_only: true,
+            ..Config::new()
         };
 
         let file = create_test_file("Hello World\nhello world");
-        let matcher = Matcher::new(&config);
-        let printer = Printer::new(&config);
+        let searcher = Searcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, Vec::new());
 
-        matcher.search_file(file.path(), &printer).unwrap();
+        assert!(searcher.matcher().is_match(b"Hello World"));
+        assert!(!searcher.matcher().is_match(b"hello world"));
+        searcher.search_path(file.path(), &mut printer).unwrap();
+        assert_eq!(printer.into_inner(), b"1\n");
     }
 
     #[test]
     fn test_case_insensitive_search() {
         let config = Config {
-            pattern: "Hello".to_string(),
+            patterns: vec!["Hello".to_string()],
             paths: vec![],
             case_sensitive: false,
-            invert_match: false,
-            line_numbers: false,
             count_only: true,
+            ..Config::new()
         };
 
         let file = create_test_file("Hello World\nhello world");
-        let matcher = Matcher::new(&config);
-        let printer = Printer::new(&config);
+        let searcher = Searcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, Vec::new());
+
+        assert!(searcher.matcher().is_match(b"hello world"));
+        searcher.search_path(file.path(), &mut printer).unwrap();
+        assert_eq!(printer.into_inner(), b"2\n");
+    }
+
+    #[test]
+    fn test_unicode_case_folding() {
+        for syntax in [PatternSyntax::Extended, PatternSyntax::Fixed] {
+            let config = Config {
+                patterns: vec!["kiss".to_string(), "stra\u{df}e".to_string()],
+                syntax,
+                case_sensitive: false,
+                ..Config::new()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+
+            assert!(matcher.is_match("\u{212a}I\u{17f}S".as_bytes()));
+            assert!(matcher.is_match("STRA\u{1e9e}E".as_bytes()));
+            // Simple folding maps one character to one: no `ss` for `ß`, and the Turkish
+            // dotless i stays apart from `i`.
+            assert!(!matcher.is_match(b"STRASSE"));
+            assert!(!matcher.is_match("k\u{131}ss".as_bytes()));
+        }
+
+        let config = Config {
+            patterns: vec!["abc".to_string()],
+            syntax: PatternSyntax::Fixed,
+            case_sensitive: false,
+            ..Config::new()
+        };
+        assert!(matches!(Matcher::new(&config).unwrap().engine, Engine::Literals(_)));
+    }
+
+    #[test]
+    fn test_fixed_strings_prefer_the_longest() {
+        let matches = |patterns: &[&str], replace: Option<&str>| {
+            let config = Config {
+                patterns: patterns.iter().map(|pattern| pattern.to_string()).collect(),
+                syntax: PatternSyntax::Fixed,
+                case_sensitive: false,
+                replace: replace.map(str::to_string),
+                ..Config::new()
+            };
+            Matcher::new(&config).unwrap().find_matches(b"xABK ab")
+        };
+        // Aho-Corasick, then the regex fallbacks for `k` folding and for --replace.
+        assert_eq!(matches(&["a", "ab"], None), vec![1..3, 5..7]);
+        assert_eq!(matches(&["a", "abk"], None), vec![1..4, 5..6]);
+        assert_eq!(matches(&["a", "ab"], Some("x")), vec![1..3, 5..7]);
+    }
+
+    #[test]
+    fn test_smart_case() {
+        let smart = |pattern: &str, syntax| Config {
+            patterns: vec![pattern.to_string()],
+            syntax,
+            smart_case: true,
+            ..Config::new()
+        };
+        let cases = [
+            ("hello", PatternSyntax::Extended, true),
+            ("Hello", PatternSyntax::Extended, false),
+            (r"\Shello\W\p{Lu}", PatternSyntax::Extended, true),
+            ("[A-Z]x", PatternSyntax::Extended, false),
+            (r"\(a\)\{2\}", PatternSyntax::Basic, true),
+            (r"\S", PatternSyntax::Fixed, false),
+        ];
+        for (pattern, syntax, ignore_case) in cases {
+            assert_eq!(ignores_case(&smart(pattern, syntax)), ignore_case, "{}", pattern);
+        }
+
+        let config = smart("hello", PatternSyntax::Extended);
+        assert!(Matcher::new(&config).unwrap().is_match(b"HELLO"));
+        let config = smart("Hello", PatternSyntax::Extended);
+        assert!(!Matcher::new(&config).unwrap().is_match(b"hello"));
+    }
+
+    #[test]
+    fn test_extended_regex_search() {
+        let config = config_with("^(foo|bar)[0-9]+$", PatternSyntax::Extended);
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match(b"foo42"));
+        assert!(matcher.is_match(b"bar7"));
+        assert!(!matcher.is_match(b"baz7"));
+        assert!(!matcher.is_match(b"xfoo42"));
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let config = config_with("o+", PatternSyntax::Extended);
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert_eq!(matcher.find_matches(b"foo boo x"), vec![1..3, 5..7]);
+        assert!(matcher.find_matches(b"bar").is_empty());
+    }
+
+    #[test]
+    fn test_multiple_fixed_strings() {
+        let config = Config {
+            patterns: vec!["id".to_string(), "ident".to_string(), "x.y".to_string()],
+            syntax: PatternSyntax::Fixed,
+            case_sensitive: false,
+            ..Config::new()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matches!(matcher.engine, Engine::Literals(_)));
+        assert_eq!(matcher.find_matches(b"IDENTITY and x.y"), vec![0..5, 13..16]);
+        assert!(!matcher.is_match(b"xzy"));
+    }
+
+    #[test]
+    fn test_multiple_regex_patterns() {
+        let config = Config {
+            patterns: vec!["^foo$".to_string(), "ba+r".to_string()],
+            ..Config::new()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match(b"foo"));
+        assert!(matcher.is_match(b"xbaar"));
+        assert!(!matcher.is_match(b"food"));
+
+        let config = Config {
+            patterns: vec!["a)|(?:b".to_string(), "c".to_string()],
+            ..Config::new()
+        };
+        assert!(matches!(Matcher::new(&config), Err(GropeError::InvalidPattern(_))));
+
+        let config = Config { patterns: vec![], ..Config::new() };
+        assert!(!Matcher::new(&config).unwrap().is_match(b"anything"));
+    }
+
+    #[test]
+    fn test_replacements() {
+        let config = Config {
+            patterns: vec![r"(\w+)=(?P<value>\d+)".to_string()],
+            replace: Some("$value:$1 $$".to_string()),
+            ..Config::new()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+
+        let line = b"a=1, bc=23";
+        let matches = matcher.find_matches(line);
+        assert_eq!(
+            matcher.replacements(line, &matches),
+            Some(vec![b"1:a $".to_vec(), b"23:bc $".to_vec()])
+        );
+
+        let config = Config { syntax: PatternSyntax::Fixed, ..config };
+        let matcher = Matcher::new(&config).unwrap();
+        assert!(matches!(matcher.engine, Engine::Regex(_)));
+    }
+
+    #[test]
+    fn test_word_regexp() {
+        let config = Config {
+            patterns: vec!["id".to_string()],
+            word_regexp: true,
+            ..Config::new()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert!(!matcher.is_match(b"identifier valid"));
+        assert_eq!(matcher.find_matches(b"valid id, (id)"), vec![6..8, 11..13]);
+
+        let config = Config { syntax: PatternSyntax::Fixed, case_sensitive: false, ..config };
+        let matcher = Matcher::new(&config).unwrap();
+        assert!(matches!(matcher.engine, Engine::Literals(_)));
+        assert!(!matcher.is_match("ident id\u{e9}".as_bytes()));
+        assert_eq!(matcher.find_matches(b"validID ID_x Id."), vec![13..15]);
+
+        // The longest hit `foo-b` is not a whole word, but the shorter `foo` is.
+        let config = Config {
+            patterns: vec!["foo-b".to_string(), "foo".to_string()],
+            syntax: PatternSyntax::Fixed,
+            word_regexp: true,
+            ..Config::new()
+        };
+        let matcher = Matcher::new(&config).unwrap();
+        assert_eq!(matcher.find_matches(b"foo-bar"), vec![0..3]);
+    }
+
+    #[test]
+    fn test_line_regexp() {
+        for syntax in [PatternSyntax::Extended, PatternSyntax::Fixed] {
+            let config = Config {
+                patterns: vec!["abc".to_string(), "ab".to_string()],
+                syntax,
+                case_sensitive: false,
+                line_regexp: true,
+                ..Config::new()
+            };
+            let matcher = Matcher::new(&config).unwrap();
+
+            assert!(matcher.is_match(b"AB"));
+            assert!(!matcher.is_match(b"abcd"));
+            assert_eq!(matcher.find_matches(b"aBc"), vec![0..3]);
+        }
+    }
+
+    #[test]
+    fn test_fixed_string_search() {
+        let config = config_with("a.b(", PatternSyntax::Fixed);
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match(b"call a.b(x)"));
+        assert!(!matcher.is_match(b"axb("));
+    }
+
+    #[test]
+    fn test_basic_regex_search() {
+        let config = config_with(r"^\(ab\)\{2\}+x|y$", PatternSyntax::Basic);
+        let matcher = Matcher::new(&config).unwrap();
+
+        assert!(matcher.is_match(b"abab+x|y"));
+        assert!(!matcher.is_match(b"ababx"));
+        assert_eq!(basic_to_extended(r"*a\|b*"), r"\*a|b*");
+        assert_eq!(basic_to_extended(r"[\[]"), r"[\\\[]");
+        assert_eq!(basic_to_extended("[[:digit:]]a$b"), r"[[:digit:]]a\$b");
+    }
+
+    #[test]
+    fn test_invalid_pattern_error() {
+        let config = config_with("foo(bar", PatternSyntax::Extended);
+        let err = Matcher::new(&config).err().expect("pattern should not compile");
 
-        matcher.search_file(file.path(), &printer).unwrap();
+        assert!(matches!(err, GropeError::InvalidPattern(_)));
+        assert!(err.to_string().contains("unclosed group"));
     }
 }
@@ -0,0 +1,381 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+use crate::cli::Config;
+use crate::error::GropeError;
+use crate::searcher::Searcher;
+use crate::printer::Printer;
+use crate::rewriter::Rewriter;
+use crate::stats::{FileStats, Stats};
+use crate::walker::Walker;
+
+/// A file to search, numbered in the order it was named or found.
+type Job = (usize, PathBuf);
+/// The output of a searched file, or the per-file error that kept it from being searched.
+type JobResult = (usize, Result<FileOutput, GropeError>);
+
+/// What was printed for one file: the output still buffered, which is empty once the file
+/// had its turn on stdout, and whether it printed lines at all.
+struct FileOutput {
+    buffer: Vec<u8>,
+    printed_lines: bool,
+    stats: FileStats,
+}
+
+/// The state of stdout shared by the workers and the collector.
+struct Output {
+    /// The file whose turn it is to write straight to stdout, or `usize::MAX` while no
+    /// file may.
+    turn: AtomicUsize,
+    /// Whether a file written so far printed lines, so the next one is separated from it.
+    printed_lines: AtomicBool,
+    /// Set once stdout is closed, as by `grope ... | head -1`; the run then stops quietly.
+    closed: AtomicBool,
+    /// Written between files with context lines, like GNU grep's `--`.
+    separator: Vec<u8>,
+}
+
+impl Output {
+    fn write_to_stdout(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes).and_then(|()| stdout.flush()).inspect_err(|e| {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                self.closed.store(true, Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+/// Output held back while it is a file's turn, before it goes to stdout in one write.
+const FLUSH_SIZE: usize = 64 * 1024;
+
+/// One file's output, which goes to stdout once it is that file's turn and is buffered
+/// until then, so files never interleave.
+///
+/// During its turn the output is still written in blocks, but every block is flushed
+/// before the input is read again, so results from a slow stream like `tail -f` show up
+/// before the search waits for more.
+struct FileSlot<'o> {
+    index: usize,
+    output: &'o Output,
+    /// Put a separator before this file's first line if an earlier file printed lines.
+    separate: bool,
+    buffer: RefCell<Vec<u8>>,
+    streaming: Cell<bool>,
+}
+
+impl<'o> FileSlot<'o> {
+    fn new(index: usize, output: &'o Output, separate: bool) -> Self {
+        let buffer = RefCell::new(Vec::new());
+        Self { index, output, separate, buffer, streaming: Cell::new(false) }
+    }
+
+    fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        let full = {
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.extend_from_slice(bytes);
+            buffer.len() >= FLUSH_SIZE
+        };
+        if full {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered output to stdout if it is this file's turn.
+    fn flush(&self) -> io::Result<()> {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        if !self.streaming.get() {
+            if self.output.turn.load(Ordering::Acquire) != self.index {
+                return Ok(());
+            }
+            self.streaming.set(true);
+            if self.separate && self.output.printed_lines.load(Ordering::Acquire) {
+                self.output.write_to_stdout(&self.output.separator)?;
+            }
+        }
+        self.output.write_to_stdout(&buffer)?;
+        buffer.clear();
+        Ok(())
+    }
+
+    /// What is left to write once the file has been searched: nothing if it is its turn.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        self.flush()?;
+        Ok(self.buffer.into_inner())
+    }
+}
+
+/// The `Write` end of a `FileSlot`, for the printer or rewriter.
+struct FileWriter<'s, 'o>(&'s FileSlot<'o>);
+
+impl Write for FileWriter<'_, '_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.write(bytes)?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Reads a file's input, first flushing what was printed for the input read so far.
+struct FlushingReader<'s, 'o, R> {
+    input: R,
+    slot: &'s FileSlot<'o>,
+}
+
+impl<R: Read> Read for FlushingReader<'_, '_, R> {
+    fn read(&mut self, bytes: &mut [u8]) -> io::Result<usize> {
+        self.slot.flush()?;
+        self.input.read(bytes)
+    }
+}
+
+/// Searches files on `config.threads` workers while the walker queues them.
+///
+/// Results are written in input order unless `--unordered` is set, in which case each
+/// file is written as soon as its worker finishes. The file whose turn it is in input
+/// order, or the only input, writes to stdout as it is searched.
+pub struct Pool<'a> {
+    config: &'a Config,
+    searcher: &'a Searcher<'a>,
+    walker: &'a Walker<'a>,
+}
+
+impl<'a> Pool<'a> {
+    pub fn new(config: &'a Config, searcher: &'a Searcher<'a>, walker: &'a Walker<'a>) -> Self {
+        Self { config, searcher, walker }
+    }
+
+    /// Searches every file and returns the totals, including how many files failed.
+    ///
+    /// Only failures to write the results end the run early with an error.
+    pub fn run(&self) -> Result<Stats, GropeError> {
+        let started = Instant::now();
+        let stop = AtomicBool::new(false);
+        let (job_tx, job_rx) = mpsc::channel();
+        let job_rx = Mutex::new(job_rx);
+        let (result_tx, result_rx) = mpsc::channel();
+        let mut separator = Vec::new();
+        Printer::new(self.config, &mut separator).print_separator()?;
+        let output = Output {
+            turn: AtomicUsize::new(self.first_turn()),
+            printed_lines: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            separator,
+        };
+
+        thread::scope(|scope| {
+            for _ in 0..self.config.threads {
+                let (job_rx, result_tx, stop) = (&job_rx, result_tx.clone(), &stop);
+                let output = &output;
+                scope.spawn(move || self.work(job_rx, result_tx, output, stop));
+            }
+            let stop_queue = &stop;
+            scope.spawn(move || self.queue_files(job_tx, result_tx, stop_queue));
+
+            // Whether collecting finished, failed or stopped at -q's first match, the
+            // remaining workers have nothing left to do.
+            let result = self.collect(result_rx, &output, started);
+            stop.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+
+    /// In input order the first file starts with its turn. Unordered output has no turns,
+    /// except for a single input, which has stdout to itself.
+    fn first_turn(&self) -> usize {
+        let single_input = self.config.paths.len() == 1 && !self.config.recursive;
+        if self.config.quiet || (self.config.unordered && !single_input) {
+            usize::MAX
+        } else {
+            0
+        }
+    }
+
+    /// Files are separated by the context separator when context lines are printed.
+    fn separates_files(&self) -> bool {
+        self.config.has_context()
+            && !self.config.count_only
+            && self.config.list_files.is_none()
+            && !self.config.json
+            && !self.config.rewrites()
+    }
+
+    fn queue_files(&self, jobs: Sender<Job>, results: Sender<JobResult>, stop: &AtomicBool) {
+        let mut index = 0;
+        for path in &self.config.paths {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            if self.config.recursive && path.is_dir() {
+                let walked = self.walker.walk(path, &mut |file| {
+                    match file {
+                        Ok(file) => {
+                            let _ = jobs.send((index, file.to_path_buf()));
+                        }
+                        Err(e) => {
+                            let _ = results.send((index, Err(e)));
+                        }
+                    }
+                    index += 1;
+                    if stop.load(Ordering::Relaxed) {
+                        ControlFlow::Break(())
+                    } else {
+                        ControlFlow::Continue(())
+                    }
+                });
+                if walked.is_break() {
+                    return;
+                }
+            } else {
+                let _ = jobs.send((index, path.clone()));
+                index += 1;
+            }
+        }
+    }
+
+    fn work(
+        &self,
+        jobs: &Mutex<Receiver<Job>>,
+        results: Sender<JobResult>,
+        output: &Output,
+        stop: &AtomicBool,
+    ) {
+        loop {
+            let job = jobs.lock().unwrap().recv();
+            let Ok((index, path)) = job else {
+                return;
+            };
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let slot = FileSlot::new(index, output, self.separates_files());
+            let result = self.search(&path, slot).map_err(|e| GropeError::File(path, e));
+            if results.send((index, result)).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn search(&self, path: &Path, slot: FileSlot<'_>) -> io::Result<FileOutput> {
+        if self.config.rewrites() {
+            let rewriter = Rewriter::new(self.config, self.searcher.matcher());
+            let stats = rewriter.rewrite_file(path, &mut FileWriter(&slot))?;
+            return Ok(FileOutput { buffer: slot.finish()?, printed_lines: false, stats });
+        }
+
+        let mut printer = Printer::new(self.config, FileWriter(&slot));
+        let stats = if path.to_str() == Some("-") {
+            let input = FlushingReader { input: io::stdin(), slot: &slot };
+            self.searcher.search_reader(path, input, &mut printer)?
+        } else {
+            let input = FlushingReader { input: File::open(path)?, slot: &slot };
+            self.searcher.search_reader(path, input, &mut printer)?
+        };
+        let printed_lines = printer.printed_lines();
+        drop(printer);
+        Ok(FileOutput { stats, printed_lines, buffer: slot.finish()? })
+    }
+
+    /// Writes the output each file buffered before its turn, handing the turn on in input
+    /// order, then the summary.
+    ///
+    /// Once stdout is closed, as by `grope ... | head -1`, collecting stops with a
+    /// `BrokenPipe` error, whichever thread noticed it.
+    fn collect(
+        &self,
+        results: Receiver<JobResult>,
+        output: &Output,
+        started: Instant,
+    ) -> Result<Stats, GropeError> {
+        let mut stats = Stats::default();
+        self.write_results(results, output, &mut stats)?;
+        if !self.config.quiet {
+            let mut summary = Vec::new();
+            Printer::new(self.config, &mut summary).print_summary(&stats, started.elapsed())?;
+            output.write_to_stdout(&summary)?;
+        }
+        io::stdout().flush()?;
+        Ok(stats)
+    }
+
+    /// Writes each file's buffered output in order and adds it to `stats`, reporting files
+    /// that could not be searched on stderr unless `-s` is set.
+    ///
+    /// With `-q` nothing is written, and collecting stops at the first file with a match.
+    fn write_results(
+        &self,
+        results: Receiver<JobResult>,
+        output: &Output,
+        stats: &mut Stats,
+    ) -> io::Result<()> {
+        // Returns whether collecting can stop because -q found its match.
+        let mut write = |result: Result<FileOutput, GropeError>| -> io::Result<bool> {
+            let file = match result {
+                Ok(file) => file,
+                // The file failed because its worker could not write to stdout.
+                Err(_) if output.closed.load(Ordering::Relaxed) => {
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                Err(e) => {
+                    stats.errors += 1;
+                    if !self.config.no_messages {
+                        eprintln!("grope: {}", e);
+                    }
+                    return Ok(false);
+                }
+            };
+            stats.add(&file.stats);
+            if self.config.quiet {
+                return Ok(file.stats.matched_lines > 0);
+            }
+            // A file that had its turn has already written everything, separator included.
+            if !file.buffer.is_empty() {
+                if self.separates_files() && output.printed_lines.load(Ordering::Acquire) {
+                    output.write_to_stdout(&output.separator)?;
+                }
+                output.write_to_stdout(&file.buffer)?;
+            }
+            if file.printed_lines {
+                output.printed_lines.store(true, Ordering::Release);
+            }
+            Ok(false)
+        };
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, result) in results {
+            // Nothing is printed with -q, so there is no order to keep.
+            if self.config.unordered || self.config.quiet {
+                if write(result)? {
+                    return Ok(());
+                }
+                continue;
+            }
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next) {
+                if write(result)? {
+                    return Ok(());
+                }
+                next += 1;
+                output.turn.store(next, Ordering::Release);
+            }
+        }
+        Ok(())
+    }
+}
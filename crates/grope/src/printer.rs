@@ -1,54 +1,569 @@
-use std::io;
+use std::io::{self, Write};
+use std::ops::Range;
 use std::path::Path;
-use crate::cli::Config;
+use std::time::Duration;
+use serde_json::Value;
+use crate::cli::{ColumnUnit, Config, ListFiles};
+use crate::color::Palette;
+use crate::json;
+use crate::sink::{Sink, SinkContext, SinkMatch};
+use crate::stats::{FileStats, Stats};
 
-pub struct Printer<'a> {
+/// How the printer renders search results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// grep-style `path:line:text` lines.
+    Standard,
+    /// One JSON object per event (`--json`), for tools that cannot parse `path:line:text`.
+    Json,
+}
+
+/// Formats search results into `out`, which is a per-file buffer when searching in parallel.
+pub struct Printer<'a, W: Write> {
     config: &'a Config,
+    mode: OutputMode,
+    palette: Option<Palette>,
+    out: W,
+    printed_lines: bool,
+    binary_match: bool,
 }
 
-impl<'a> Printer<'a> {
-    pub fn new(config: &'a Config) -> Self {
-        Self { config }
+impl<'a, W: Write> Printer<'a, W> {
+    pub fn new(config: &'a Config, out: W) -> Self {
+        let mode = if config.json { OutputMode::Json } else { OutputMode::Standard };
+        Self {
+            config,
+            mode,
+            palette: Palette::for_choice(config.color).filter(|_| mode == OutputMode::Standard),
+            out,
+            printed_lines: false,
+            binary_match: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Whether any match or context line has been printed so far.
+    pub fn printed_lines(&self) -> bool {
+        self.printed_lines
+    }
+
+    /// Prints the `--` line that goes between groups of context and matching lines.
+    pub fn print_separator(&mut self) -> io::Result<()> {
+        let separator = self.paint(|palette| &palette.separator, "--");
+        writeln!(self.out, "{}", separator)
+    }
+
+    /// Prints the totals for the whole run, after every file has finished.
+    ///
+    /// JSON output always ends with a summary record; otherwise totals are printed for
+    /// `--write` and `--dry-run`, and with `--stats`.
+    pub fn print_summary(&mut self, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+        if self.mode == OutputMode::Json {
+            return self.write_json(json::summary(stats, elapsed));
+        }
+        if self.config.rewrites() {
+            let verb = if self.config.dry_run { "Would replace" } else { "Replaced" };
+            let matches = plural(stats.matches, "match", "matches");
+            let files = plural(stats.searches_with_match, "file", "files");
+            writeln!(
+                self.out,
+                "{} {} {} in {} {}",
+                verb, stats.matches, matches, stats.searches_with_match, files
+            )?;
+        }
+        if self.config.stats {
+            self.print_stats(stats, elapsed)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the `--stats` totals, set apart from the results by a blank line.
+    fn print_stats(&mut self, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+        let lines = plural(stats.matched_lines, "line", "lines");
+        let matches = plural(stats.matches, "match", "matches");
+        let files = plural(stats.searches, "file", "files");
+        let contained = plural(stats.searches_with_match, "file", "files");
+        writeln!(self.out)?;
+        writeln!(self.out, "{} matched {}", stats.matched_lines, lines)?;
+        writeln!(self.out, "{} {}", stats.matches, matches)?;
+        writeln!(self.out, "{} {} searched", stats.searches, files)?;
+        writeln!(self.out, "{} {} contained matches", stats.searches_with_match, contained)?;
+        writeln!(self.out, "{} bytes searched", stats.bytes_searched)?;
+        writeln!(self.out, "{:.6} seconds elapsed", elapsed.as_secs_f64())
     }
 
-    pub fn print_match(&self, path: &Path, line_number: usize, line: &str) -> io::Result<()> {
-        if self.config.paths.len() > 1 {
-            print!("{}:", path.display());
+    /// Writes the file name, line number, column and byte offset that are enabled, each
+    /// followed by `separator`.
+    fn write_prefix(
+        &mut self,
+        path: &Path,
+        line_number: usize,
+        column: Option<usize>,
+        offset: u64,
+        separator: char,
+    ) -> io::Result<()> {
+        let separator = self.paint(|palette| &palette.separator, &separator.to_string());
+        if self.config.with_filename() {
+            let path = self.paint(|palette| &palette.filename, &display_name(path));
+            write!(self.out, "{}", path)?;
+            self.end_file_name(&separator)?;
+        }
+        if self.config.line_numbers || self.config.vimgrep {
+            let line_number = self.paint(|palette| &palette.line_number, &line_number.to_string());
+            write!(self.out, "{}{}", line_number, separator)?;
         }
-        if self.config.line_numbers {
-            print!("{}:", line_number);
+        if let Some(column) = column {
+            // GREP_COLORS has no capability for columns, so they look like line numbers.
+            let column = self.paint(|palette| &palette.line_number, &column.to_string());
+            write!(self.out, "{}{}", column, separator)?;
+        }
+        if self.config.byte_offset {
+            let offset = self.paint(|palette| &palette.byte_offset, &offset.to_string());
+            write!(self.out, "{}{}", offset, separator)?;
         }
-        println!("{}", line);
         Ok(())
     }
 
-    pub fn print_count(&self, path: &Path, count: usize) -> io::Result<()> {
-        if self.config.paths.len() > 1 {
-            println!("{}:{}", path.display(), count);
+    fn write_line(
+        &mut self,
+        line: &[u8],
+        matches: &[Range<usize>],
+        replacements: Option<&[Vec<u8>]>,
+    ) -> io::Result<()> {
+        if self.palette.is_none() && replacements.is_none() {
+            write!(self.out, "{}", String::from_utf8_lossy(line))?;
+            return self.end_line();
+        }
+
+        let mut last = 0;
+        for (i, range) in matches.iter().enumerate() {
+            let matched = replacements.map_or(&line[range.clone()], |r| &r[i]);
+            let matched = self.paint(|palette| &palette.matched, &String::from_utf8_lossy(matched));
+            write!(self.out, "{}", String::from_utf8_lossy(&line[last..range.start]))?;
+            write!(self.out, "{}", matched)?;
+            last = range.end;
+        }
+        write!(self.out, "{}", String::from_utf8_lossy(&line[last..]))?;
+        self.end_line()
+    }
+
+    /// Ends a matching or context line, with a NUL byte instead of a newline for
+    /// `--null-data`.
+    fn end_line(&mut self) -> io::Result<()> {
+        self.out.write_all(&[self.config.line_terminator()])
+    }
+
+    /// Follows a file name with `separator`, or with a NUL byte for `-Z`, which cannot
+    /// appear in file names.
+    fn end_file_name(&mut self, separator: &str) -> io::Result<()> {
+        if self.config.null {
+            self.out.write_all(b"\0")
         } else {
-            println!("{}", count);
+            write!(self.out, "{}", separator)
+        }
+    }
+
+    /// The column to print for the match at `range`, if `--column` or `--vimgrep` asked
+    /// for one. Lines selected by `-v` have no matches and start at column 1.
+    fn column(&self, line: &SinkMatch<'_>, range: Option<&Range<usize>>) -> Option<usize> {
+        if self.config.column.is_none() && !self.config.vimgrep {
+            return None;
+        }
+        let unit = self.config.column.unwrap_or(ColumnUnit::Bytes);
+        Some(range.map_or(1, |range| line.match_column(range, unit)))
+    }
+
+    fn print_count(&mut self, path: &Path, count: u64) -> io::Result<()> {
+        if self.config.with_filename() {
+            let path = self.paint(|palette| &palette.filename, &display_name(path));
+            let separator = self.paint(|palette| &palette.separator, ":");
+            write!(self.out, "{}", path)?;
+            self.end_file_name(&separator)?;
         }
+        writeln!(self.out, "{}", count)
+    }
+
+    fn write_json(&mut self, event: Value) -> io::Result<()> {
+        writeln!(self.out, "{}", event)
+    }
+
+    fn paint(&self, sgr: impl Fn(&Palette) -> &String, text: &str) -> String {
+        match &self.palette {
+            Some(palette) => Palette::paint(sgr(palette), text),
+            None => text.to_string(),
+        }
+    }
+}
+
+impl<W: Write> Sink for Printer<'_, W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        self.binary_match = false;
+        match self.mode {
+            OutputMode::Standard => Ok(()),
+            OutputMode::Json => self.write_json(json::begin(path)),
+        }
+    }
+
+    /// Prints a selected line, highlighting its matches when colors are on. With `-o`, each
+    /// match is printed on its own line instead.
+    ///
+    /// With `--replace`, the expanded template for each match is printed in place of the
+    /// matched text. Invalid UTF-8 is printed lossily, with U+FFFD for each bad sequence.
+    fn matched(&mut self, path: &Path, line: &SinkMatch<'_>) -> io::Result<bool> {
+        match self.mode {
+            OutputMode::Standard if self.config.only_matching => {
+                for (i, range) in line.matches.iter().enumerate() {
+                    let column = self.column(line, Some(range));
+                    let offset = line.match_offset(range);
+                    self.write_prefix(path, line.line_number, column, offset, ':')?;
+                    let matched = line.replacements.map_or(&line.line[range.clone()], |r| &r[i]);
+                    let matched = String::from_utf8_lossy(matched);
+                    let matched = self.paint(|palette| &palette.matched, &matched);
+                    write!(self.out, "{}", matched)?;
+                    self.end_line()?;
+                    self.printed_lines = true;
+                }
+                return Ok(true);
+            }
+            // Every match gets its own line, so that each can be jumped to.
+            OutputMode::Standard if self.config.vimgrep && line.matches.len() > 1 => {
+                for range in line.matches {
+                    let column = self.column(line, Some(range));
+                    self.write_prefix(path, line.line_number, column, line.offset, ':')?;
+                    self.write_line(line.line, line.matches, line.replacements)?;
+                }
+            }
+            OutputMode::Standard => {
+                let column = self.column(line, line.matches.first());
+                self.write_prefix(path, line.line_number, column, line.offset, ':')?;
+                self.write_line(line.line, line.matches, line.replacements)?
+            }
+            OutputMode::Json => {
+                let event = json::line(
+                    "match",
+                    path,
+                    line.line_number,
+                    line.offset,
+                    line.line,
+                    line.matches,
+                    line.replacements,
+                );
+                self.write_json(event)?
+            }
+        }
+        self.printed_lines = true;
+        Ok(true)
+    }
+
+    /// Prints a context line, using `-` after the path and line number like GNU grep.
+    ///
+    /// `-o` prints only matches, so context lines are skipped, but groups stay separated.
+    fn context(&mut self, path: &Path, line: &SinkContext<'_>) -> io::Result<bool> {
+        let SinkContext { line_number, offset, line } = *line;
+        match self.mode {
+            OutputMode::Standard if self.config.only_matching => return Ok(true),
+            OutputMode::Standard => {
+                self.write_prefix(path, line_number, None, offset, '-')?;
+                self.write_line(line, &[], None)?
+            }
+            OutputMode::Json => {
+                let event = json::line("context", path, line_number, offset, line, &[], None);
+                self.write_json(event)?
+            }
+        }
+        self.printed_lines = true;
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        if self.printed_lines && self.mode == OutputMode::Standard {
+            self.print_separator()?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the matching lines of a binary file with a single notice.
+    fn binary_match(&mut self, path: &Path) -> io::Result<()> {
+        self.binary_match = true;
+        if self.mode == OutputMode::Json {
+            return Ok(());
+        }
+        writeln!(self.out, "Binary file {} matches", display_name(path))?;
+        self.printed_lines = true;
         Ok(())
     }
+
+    fn finish(&mut self, path: &Path, stats: &FileStats) -> io::Result<()> {
+        match self.mode {
+            OutputMode::Standard if self.config.list_files.is_some() => {
+                let matched = stats.matched_lines > 0;
+                if matched == (self.config.list_files == Some(ListFiles::WithMatches)) {
+                    let name = self.paint(|palette| &palette.filename, &display_name(path));
+                    write!(self.out, "{}", name)?;
+                    self.end_file_name("\n")?;
+                }
+                Ok(())
+            }
+            OutputMode::Standard if self.config.count_only => {
+                self.print_count(path, stats.matched_lines)
+            }
+            OutputMode::Standard => Ok(()),
+            OutputMode::Json => self.write_json(json::end(path, self.binary_match, stats)),
+        }
+    }
+}
+
+fn plural(count: u64, one: &'static str, many: &'static str) -> &'static str {
+    if count == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// The name printed for `path`, with GNU grep's label for standard input.
+fn display_name(path: &Path) -> String {
+    if path.to_str() == Some("-") {
+        "(standard input)".to_string()
+    } else {
+        path.display().to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use crate::cli::ColorChoice;
+
+    fn context(line_number: usize, offset: u64, line: &[u8]) -> SinkContext<'_> {
+        SinkContext { line_number, offset, line }
+    }
 
     #[test]
     fn test_print_match_with_line_numbers() {
         let config = Config {
-            pattern: "test".to_string(),
+            patterns: vec!["test".to_string()],
             paths: vec![PathBuf::from("file.txt")],
-            case_sensitive: true,
-            invert_match: false,
             line_numbers: true,
-            count_only: false,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+
+        let mut printer = Printer::new(&config, Vec::new());
+        let path = Path::new("file.txt");
+        let matches = [0..4, 5..9];
+        printer.matched(path, &SinkMatch::new(1, 0, b"test line", &matches)).unwrap();
+        assert_eq!(printer.into_inner(), b"1:test line\n");
+    }
+
+    #[test]
+    fn test_print_context_and_separator() {
+        let config = Config {
+            paths: vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")],
+            line_numbers: true,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+
+        let mut printer = Printer::new(&config, Vec::new());
+        let path = Path::new("a.txt");
+        printer.context_break().unwrap();
+        printer.context(path, &context(1, 0, b"before")).unwrap();
+        printer.matched(path, &SinkMatch::new(2, 7, b"match", &[])).unwrap();
+        printer.context_break().unwrap();
+        printer.context(path, &context(9, 50, b"after")).unwrap();
+
+        assert!(printer.printed_lines());
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "a.txt-1-before\na.txt:2:match\n--\na.txt-9-after\n"
+        );
+    }
+
+    #[test]
+    fn test_only_matching_with_offsets() {
+        let config = Config {
+            paths: vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")],
+            line_numbers: true,
+            only_matching: true,
+            byte_offset: true,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.context(Path::new("a.txt"), &context(1, 0, b"before")).unwrap();
+        let matches = [3..5, 9..12];
+        let line = SinkMatch::new(2, 7, b"id=12 id=345", &matches);
+        printer.matched(Path::new("a.txt"), &line).unwrap();
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "a.txt:2:10:12\na.txt:2:16:345\n"
+        );
+
+        let config = Config { only_matching: false, ..config };
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.context(Path::new("a.txt"), &context(1, 0, b"before")).unwrap();
+        assert_eq!(printer.into_inner(), b"a.txt-1-0-before\n");
+    }
+
+    #[test]
+    fn test_print_replacements() {
+        let config = Config {
+            replace: Some("<$1>".to_string()),
+            color: ColorChoice::Never,
+            ..Config::new()
         };
+        let replacements = [b"<12>".to_vec(), b"<345>".to_vec()];
+
+        let matches = [0..5, 6..12];
+        let line = SinkMatch::new(1, 0, b"id=12 id=345", &matches).with_replacements(&replacements);
+
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.matched(Path::new("-"), &line).unwrap();
+        assert_eq!(printer.into_inner(), b"<12> <345>\n");
+
+        let config = Config { only_matching: true, ..config };
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.matched(Path::new("-"), &line).unwrap();
+        assert_eq!(printer.into_inner(), b"<12>\n<345>\n");
+    }
+
+    #[test]
+    fn test_print_stats() {
+        let config = Config { stats: true, ..Config::new() };
+        let stats = Stats {
+            searches: 3,
+            searches_with_match: 1,
+            matched_lines: 1,
+            matches: 2,
+            bytes_searched: 4096,
+            errors: 0,
+        };
+
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.print_summary(&stats, Duration::from_millis(1500)).unwrap();
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            concat!(
+                "\n1 matched line\n2 matches\n3 files searched\n1 file contained matches\n",
+                "4096 bytes searched\n1.500000 seconds elapsed\n",
+            )
+        );
+
+        let config = Config { stats: false, ..config };
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.print_summary(&stats, Duration::from_millis(1500)).unwrap();
+        assert!(printer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_columns() {
+        let config = Config {
+            paths: vec![PathBuf::from("f")],
+            column: Some(ColumnUnit::Bytes),
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let line = "h\u{e9} x x".as_bytes();
+        let matches = [4..5, 6..7];
+        let matched = SinkMatch::new(2, 10, line, &matches);
+
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.matched(Path::new("f"), &matched).unwrap();
+        printer.matched(Path::new("f"), &SinkMatch::new(3, 20, b"inverted", &[])).unwrap();
+        assert_eq!(printer.into_inner(), "5:h\u{e9} x x\n1:inverted\n".as_bytes());
+
+        let only = Config { only_matching: true, column: Some(ColumnUnit::Chars), ..config };
+        let mut printer = Printer::new(&only, Vec::new());
+        printer.matched(Path::new("f"), &matched).unwrap();
+        assert_eq!(printer.into_inner(), b"4:x\n6:x\n");
+
+        let vimgrep = Config { only_matching: false, column: None, vimgrep: true, ..only };
+        let mut printer = Printer::new(&vimgrep, Vec::new());
+        printer.matched(Path::new("f"), &matched).unwrap();
+        let expected = "f:2:5:h\u{e9} x x\nf:2:7:h\u{e9} x x\n";
+        assert_eq!(printer.into_inner(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_stdin_has_one_name() {
+        let config = Config {
+            paths: vec![PathBuf::from("-"), PathBuf::from("t.txt")],
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let stats = FileStats { matched_lines: 1, ..FileStats::default() };
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.matched(Path::new("-"), &SinkMatch::new(1, 0, b"hit", &[])).unwrap();
+        assert_eq!(printer.into_inner(), b"(standard input):hit\n");
+
+        let count = Config { count_only: true, ..config };
+        let mut printer = Printer::new(&count, Vec::new());
+        printer.finish(Path::new("-"), &stats).unwrap();
+        assert_eq!(printer.into_inner(), b"(standard input):1\n");
+    }
+
+    #[test]
+    fn test_null_separators() {
+        let config = Config {
+            paths: vec![PathBuf::from("a:b"), PathBuf::from("c")],
+            line_numbers: true,
+            null: true,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let stats = FileStats { matched_lines: 2, ..FileStats::default() };
+        let path = Path::new("a:b");
+
+        let mut printer = Printer::new(&config, Vec::new());
+        printer.matched(path, &SinkMatch::new(3, 0, b"hit", &[])).unwrap();
+        assert_eq!(printer.into_inner(), b"a:b\x003:hit\n");
+
+        let list = Config { list_files: Some(ListFiles::WithMatches), ..config };
+        let mut printer = Printer::new(&list, Vec::new());
+        printer.finish(path, &stats).unwrap();
+        assert_eq!(printer.into_inner(), b"a:b\x00");
+
+        let count = Config { list_files: None, count_only: true, ..list };
+        let mut printer = Printer::new(&count, Vec::new());
+        printer.finish(path, &stats).unwrap();
+        assert_eq!(printer.into_inner(), b"a:b\x002\n");
+
+        let data = Config { count_only: false, null: false, null_data: true, ..count };
+        let mut printer = Printer::new(&data, Vec::new());
+        let context = SinkContext { line_number: 1, offset: 0, line: b"x\ny" };
+        printer.context(path, &context).unwrap();
+        assert_eq!(printer.into_inner(), b"a:b-1-x\ny\x00");
+    }
+
+    #[test]
+    fn test_json_mode() {
+        let config = Config {
+            json: true,
+            color: ColorChoice::Always,
+            ..Config::new()
+        };
+        let stats = FileStats { matched_lines: 1, matches: 1, bytes_searched: 12 };
+
+        let mut printer = Printer::new(&config, Vec::new());
+        let path = Path::new("a.txt");
+        printer.begin(path).unwrap();
+        printer.context(path, &context(1, 0, b"ctx")).unwrap();
+        printer.context_break().unwrap();
+        printer.matched(path, &SinkMatch::new(2, 4, b"hit hit", &[0..3, 4..7])).unwrap();
+        printer.finish(path, &stats).unwrap();
 
-        let printer = Printer::new(&config);
-        printer.print_match(Path::new("file.txt"), 1, "test line").unwrap();
+        let output = String::from_utf8(printer.into_inner()).unwrap();
+        let types: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["type"].clone())
+            .collect();
+        assert_eq!(types, vec!["begin", "context", "match", "end"]);
+        assert!(!output.contains('\x1b'));
     }
 }
@@ -0,0 +1,424 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use encoding_rs::UTF_8;
+use tempfile::NamedTempFile;
+use crate::cli::{BinaryFiles, Config};
+use crate::encoding::{self, Transcoder};
+use crate::matcher::{Matcher, BUFFER_SIZE};
+use crate::stats::FileStats;
+
+/// Unchanged lines shown around each change in `--dry-run` diffs, as with `diff -u`.
+const DIFF_CONTEXT: usize = 3;
+
+/// Applies the `--replace` template to files for `--write`, or previews the edits as a
+/// unified diff with `--dry-run`.
+///
+/// Files are streamed line by line, so only the current line, plus the current hunk of a
+/// diff, is ever held in memory.
+pub struct Rewriter<'a> {
+    config: &'a Config,
+    matcher: &'a Matcher<'a>,
+}
+
+impl<'a> Rewriter<'a> {
+    pub fn new(config: &'a Config, matcher: &'a Matcher<'a>) -> Self {
+        Self { config, matcher }
+    }
+
+    /// Rewrites `path` in place, or writes the diff of what would change to `out` with
+    /// `--dry-run`. The stats count the changed lines and the replacements made.
+    ///
+    /// The new contents go to a temporary file beside `path`, or beside the file it links
+    /// to, which is renamed over it with the original permissions, so readers never see a
+    /// half-written file. The temporary file is only created at the first changed line, so
+    /// files without a match are left untouched, and so are binary and UTF-16 files, which
+    /// are reported as errors when they match.
+    pub fn rewrite_file<W: Write>(&self, path: &Path, out: &mut W) -> io::Result<FileStats> {
+        if path.to_str() == Some("-") {
+            let message = "standard input cannot be rewritten";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        let mut reader = BufReader::with_capacity(BUFFER_SIZE, File::open(path)?);
+
+        // Replacements are UTF-8, so only UTF-8 files can take them; a UTF-8 BOM is kept
+        // but not matched against, like when searching.
+        let bom = match encoding::detect(reader.fill_buf()?, None) {
+            (Some(encoding), _) if encoding != UTF_8 => {
+                let transcoder = Transcoder::new(encoding, b'\n');
+                let reason = format!("{} file matches, only UTF-8 is rewritten", encoding.name());
+                return self.skip(&mut reader, transcoder, &reason);
+            }
+            (_, bom_length) => {
+                let bom = reader.fill_buf()?[..bom_length].to_vec();
+                reader.consume(bom_length);
+                bom
+            }
+        };
+
+        // Substituting inside a binary file would most likely corrupt it.
+        if self.config.binary_files != BinaryFiles::Text && reader.fill_buf()?.contains(&0) {
+            return self.skip(&mut reader, None, "binary file matches, use -a to rewrite it");
+        }
+
+        if self.config.dry_run {
+            let mut diff = Diff::new(path, out);
+            let stats = self.edit(&mut reader, |old, new| diff.line(old, new))?;
+            diff.finish()?;
+            return Ok(stats);
+        }
+
+        // Renaming over a symbolic link would replace the link instead of its target.
+        let path = fs::canonicalize(path)?;
+        let mut writer: Option<BufWriter<NamedTempFile>> = None;
+        let mut unchanged = bom.len() as u64;
+        let stats = self.edit(&mut reader, |old, new| match &mut writer {
+            Some(writer) => writer.write_all(new),
+            None if old == new => {
+                unchanged += old.len() as u64;
+                Ok(())
+            }
+            None => {
+                let temp = writer.insert(start_rewrite(&path, unchanged)?);
+                temp.write_all(new)
+            }
+        })?;
+        let Some(writer) = writer else {
+            return Ok(stats);
+        };
+
+        let temp = writer.into_inner().map_err(|e| e.into_error())?;
+        temp.as_file().set_permissions(fs::metadata(&path)?.permissions())?;
+        temp.persist(&path).map_err(|e| e.error)?;
+        Ok(stats)
+    }
+
+    /// Leaves a file alone, failing with `reason` if it has a match that is not replaced.
+    fn skip(
+        &self,
+        reader: &mut dyn BufRead,
+        mut transcoder: Option<Transcoder>,
+        reason: &str,
+    ) -> io::Result<FileStats> {
+        let mut line = Vec::new();
+        loop {
+            let read = match &mut transcoder {
+                Some(transcoder) => transcoder.read_line(reader, &mut line)?,
+                None => {
+                    line.clear();
+                    let read = reader.read_until(b'\n', &mut line)?;
+                    encoding::strip_terminator(&mut line, b'\n');
+                    read
+                }
+            };
+            if read == 0 {
+                return Ok(FileStats::default());
+            }
+            if self.matcher.is_match(&line) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+            }
+        }
+    }
+
+    /// Calls `emit` with every line of `reader` and its rewritten form, both with their
+    /// original line terminator.
+    fn edit<F>(&self, reader: &mut dyn BufRead, mut emit: F) -> io::Result<FileStats>
+    where
+        F: FnMut(&[u8], &[u8]) -> io::Result<()>,
+    {
+        let mut stats = FileStats::default();
+        let mut line = Vec::new();
+        let mut rewritten = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            stats.bytes_searched += read as u64;
+
+            // Match without the terminator, so that `$` anchors work as when searching.
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            let content = content.strip_suffix(b"\r").unwrap_or(content);
+            let matches = self.matcher.find_matches(content);
+            if matches.is_empty() {
+                emit(&line, &line)?;
+                continue;
+            }
+
+            let replacements = self.matcher.replacements(content, &matches).unwrap_or_default();
+            rewritten.clear();
+            let mut last = 0;
+            for (range, replacement) in matches.iter().zip(&replacements) {
+                rewritten.extend_from_slice(&line[last..range.start]);
+                rewritten.extend_from_slice(replacement);
+                last = range.end;
+            }
+            rewritten.extend_from_slice(&line[last..]);
+
+            stats.matched_lines += 1;
+            stats.matches += matches.len() as u64;
+            emit(&line, &rewritten)?;
+        }
+        Ok(stats)
+    }
+}
+
+/// Creates the temporary file that replaces `path`, starting with the first `unchanged`
+/// bytes of `path` itself.
+fn start_rewrite(path: &Path, unchanged: u64) -> io::Result<BufWriter<NamedTempFile>> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut writer = BufWriter::new(NamedTempFile::new_in(dir)?);
+    io::copy(&mut File::open(path)?.take(unchanged), &mut writer)?;
+    Ok(writer)
+}
+
+/// Builds a unified diff from pairs of old and new lines.
+///
+/// Replacements never add or remove lines, so both sides of a hunk have the same start
+/// and length.
+struct Diff<'p, W: Write> {
+    path: &'p Path,
+    out: W,
+    line_number: usize,
+    /// Unchanged lines since the last change, kept for the context around hunks.
+    unchanged: VecDeque<Vec<u8>>,
+    /// The open hunk's first line number, and its lines so far.
+    hunk: Option<(usize, usize)>,
+    body: Vec<u8>,
+    /// The `-` and `+` lines of consecutive changes, printed as two blocks like `diff`.
+    removed: Vec<u8>,
+    added: Vec<u8>,
+    header_written: bool,
+}
+
+impl<'p, W: Write> Diff<'p, W> {
+    fn new(path: &'p Path, out: W) -> Self {
+        Self {
+            path,
+            out,
+            line_number: 0,
+            unchanged: VecDeque::new(),
+            hunk: None,
+            body: Vec::new(),
+            removed: Vec::new(),
+            added: Vec::new(),
+            header_written: false,
+        }
+    }
+
+    fn line(&mut self, old: &[u8], new: &[u8]) -> io::Result<()> {
+        self.line_number += 1;
+
+        if old != new {
+            let context = self.unchanged.len();
+            let (_, len) = self.hunk.get_or_insert((self.line_number - context, 0));
+            *len += context + 1;
+            for line in self.unchanged.drain(..) {
+                push_line(&mut self.body, b' ', &line);
+            }
+            push_line(&mut self.removed, b'-', old);
+            push_line(&mut self.added, b'+', new);
+            return Ok(());
+        }
+
+        self.end_change();
+        self.unchanged.push_back(old.to_vec());
+        if self.hunk.is_none() {
+            if self.unchanged.len() > DIFF_CONTEXT {
+                self.unchanged.pop_front();
+            }
+        } else if self.unchanged.len() > 2 * DIFF_CONTEXT {
+            // Too far from the last change to share a hunk with the next one.
+            self.write_hunk()?;
+            while self.unchanged.len() > DIFF_CONTEXT {
+                self.unchanged.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.end_change();
+        if self.hunk.is_some() {
+            self.write_hunk()?;
+        }
+        Ok(())
+    }
+
+    fn end_change(&mut self) {
+        self.body.append(&mut self.removed);
+        self.body.append(&mut self.added);
+    }
+
+    /// Writes the open hunk with up to `DIFF_CONTEXT` trailing unchanged lines.
+    fn write_hunk(&mut self) -> io::Result<()> {
+        let Some((start, mut len)) = self.hunk.take() else {
+            return Ok(());
+        };
+        for line in self.unchanged.iter().take(DIFF_CONTEXT) {
+            push_line(&mut self.body, b' ', line);
+            len += 1;
+        }
+
+        if !self.header_written {
+            let path = self.path.display();
+            writeln!(self.out, "--- {}\n+++ {}", path, path)?;
+            self.header_written = true;
+        }
+        writeln!(self.out, "@@ -{},{} +{},{} @@", start, len, start, len)?;
+        self.out.write_all(&self.body)?;
+        self.body.clear();
+        Ok(())
+    }
+}
+
+fn push_line(body: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+    body.push(prefix);
+    body.extend_from_slice(line);
+    if !line.ends_with(b"\n") {
+        body.extend_from_slice(b"\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(content: &str, config: &Config) -> (String, String, FileStats) {
+        let mut file = NamedTempFile::new().unwrap();
+        write!(file, "{}", content).unwrap();
+        let matcher = Matcher::new(config).unwrap();
+
+        let mut out = Vec::new();
+        let stats = Rewriter::new(config, &matcher).rewrite_file(file.path(), &mut out).unwrap();
+        let rewritten = fs::read_to_string(file.path()).unwrap();
+        let diff = String::from_utf8(out).unwrap().replace(&file.path().display().to_string(), "f");
+        (rewritten, diff, stats)
+    }
+
+    fn config(dry_run: bool) -> Config {
+        Config {
+            patterns: vec![r"old_(\w+)".to_string()],
+            replace: Some("new_$1".to_string()),
+            write: !dry_run,
+            dry_run,
+            ..Config::new()
+        }
+    }
+
+    #[test]
+    fn test_write_in_place() {
+        let (rewritten, diff, stats) = rewrite("old_a old_b\nkeep\r\nold_c", &config(false));
+
+        assert_eq!(rewritten, "new_a new_b\nkeep\r\nnew_c");
+        assert_eq!(diff, "");
+        assert_eq!(stats.matched_lines, 2);
+        assert_eq!(stats.matches, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file = NamedTempFile::new().unwrap();
+        fs::write(file.path(), "old_x\n").unwrap();
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o640)).unwrap();
+        let config = config(false);
+        let matcher = Matcher::new(&config).unwrap();
+
+        Rewriter::new(&config, &matcher).rewrite_file(file.path(), &mut Vec::new()).unwrap();
+        let metadata = fs::metadata(file.path()).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "new_x\n");
+    }
+
+    #[test]
+    fn test_write_starts_at_the_first_change() {
+        let (rewritten, _, stats) = rewrite("\u{feff}keep\r\nsame\nold_a\nold_b", &config(false));
+        assert_eq!(rewritten, "\u{feff}keep\r\nsame\nnew_a\nnew_b");
+        assert_eq!(stats.matches, 2);
+
+        // Without a change, nothing is created beside the file.
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("f");
+        fs::write(&file, "keep\n").unwrap();
+        let config = config(false);
+        let matcher = Matcher::new(&config).unwrap();
+        let modified = fs::metadata(dir.path()).unwrap().modified().unwrap();
+        Rewriter::new(&config, &matcher).rewrite_file(&file, &mut Vec::new()).unwrap();
+        assert_eq!(fs::metadata(dir.path()).unwrap().modified().unwrap(), modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_through_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "old_q\n").unwrap();
+        std::os::unix::fs::symlink("target.txt", &link).unwrap();
+        let config = config(false);
+        let matcher = Matcher::new(&config).unwrap();
+
+        Rewriter::new(&config, &matcher).rewrite_file(&link, &mut Vec::new()).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new_q\n");
+    }
+
+    #[test]
+    fn test_skipped_files_are_reported_when_they_match() {
+        let config = config(false);
+        let matcher = Matcher::new(&config).unwrap();
+        let rewriter = Rewriter::new(&config, &matcher);
+        let utf16 = |text: &str| {
+            let mut bytes = b"\xff\xfe".to_vec();
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+        let binary = |text: &str| format!("{}\0\n", text).into_bytes();
+
+        for encode in [&utf16 as &dyn Fn(&str) -> Vec<u8>, &binary] {
+            let file = NamedTempFile::new().unwrap();
+            let content = encode("x\nold_a");
+            fs::write(file.path(), &content).unwrap();
+            let err = rewriter.rewrite_file(file.path(), &mut Vec::new()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(fs::read(file.path()).unwrap(), content);
+
+            fs::write(file.path(), encode("x\nnew_a")).unwrap();
+            let stats = rewriter.rewrite_file(file.path(), &mut Vec::new()).unwrap();
+            assert_eq!(stats, FileStats::default());
+        }
+    }
+
+    #[test]
+    fn test_write_keeps_utf8_bom() {
+        let (rewritten, _, stats) = rewrite("\u{feff}old_a\n", &config(false));
+        assert_eq!(rewritten, "\u{feff}new_a\n");
+        assert_eq!(stats.matches, 1);
+    }
+
+    #[test]
+    fn test_dry_run_diff() {
+        let lines: Vec<String> = (1..=12).map(|i| format!("line {}", i)).collect();
+        let mut content = lines.join("\n");
+        content = content.replace("line 2\n", "old_2\n").replace("line 12", "old_12");
+
+        let (rewritten, diff, stats) = rewrite(&content, &config(true));
+        assert_eq!(rewritten, content);
+        assert_eq!(stats.matches, 2);
+        assert_eq!(
+            diff,
+            concat!(
+                "--- f\n+++ f\n",
+                "@@ -1,5 +1,5 @@\n line 1\n-old_2\n+new_2\n line 3\n line 4\n line 5\n",
+                "@@ -9,4 +9,4 @@\n line 9\n line 10\n line 11\n",
+                "-old_12\n\\ No newline at end of file\n",
+                "+new_12\n\\ No newline at end of file\n",
+            )
+        );
+    }
+}
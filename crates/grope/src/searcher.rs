@@ -0,0 +1,803 @@
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::Path;
+use encoding_rs::Encoding;
+use crate::cli::{BinaryFiles, Config};
+use crate::decompress;
+use crate::encoding::{self, Transcoder};
+use crate::error::GropeError;
+use crate::matcher::{Matcher, BUFFER_SIZE};
+use crate::sink::{Sink, SinkContext, SinkMatch};
+use crate::stats::FileStats;
+
+/// Searches inputs line by line and reports selected and context lines to a `Sink`.
+///
+/// ```no_run
+/// use grope::{Config, Printer, Searcher};
+///
+/// let config = Config::builder().pattern("TODO").line_numbers(true).build()?;
+/// let searcher = Searcher::new(&config)?;
+/// let mut printer = Printer::new(&config, Vec::new());
+/// searcher.search_reader("notes.txt", &b"a TODO\n"[..], &mut printer)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Searcher<'a> {
+    config: &'a Config,
+    matcher: Matcher<'a>,
+}
+
+impl<'a> Searcher<'a> {
+    /// Compiles the configured patterns once, so the searcher can be reused for every input.
+    pub fn new(config: &'a Config) -> Result<Self, GropeError> {
+        Ok(Self { config, matcher: Matcher::new(config)? })
+    }
+
+    pub fn matcher(&self) -> &Matcher<'a> {
+        &self.matcher
+    }
+
+    /// Searches the file at `path`, or standard input when `path` is `-`.
+    pub fn search_path<S: Sink>(&self, path: &Path, sink: &mut S) -> io::Result<FileStats> {
+        if path.to_str() == Some("-") {
+            self.search_reader(path, io::stdin(), sink)
+        } else {
+            self.search_reader(path, File::open(path)?, sink)
+        }
+    }
+
+    /// Searches everything `reader` produces, reporting it to `sink` under the name `path`.
+    ///
+    /// Lines are searched as raw bytes, so invalid UTF-8 never aborts the search.
+    pub fn search_reader<P, R, S>(&self, path: P, reader: R, sink: &mut S) -> io::Result<FileStats>
+    where
+        P: AsRef<Path>,
+        R: Read,
+        S: Sink,
+    {
+        let path = path.as_ref();
+        let mut reader: Box<dyn BufRead + '_> =
+            Box::new(BufReader::with_capacity(BUFFER_SIZE, reader));
+        if self.config.search_zip {
+            reader = decompress::decoder(reader)?;
+        }
+
+        let (file_encoding, bom_length) =
+            encoding::detect(reader.fill_buf()?, self.config.encoding);
+        reader.consume(bom_length);
+        let terminator = self.config.line_terminator();
+        let transcoder = file_encoding.and_then(|encoding| Transcoder::new(encoding, terminator));
+
+        // Like GNU grep, a NUL byte in the first block marks the whole file as binary, except
+        // in UTF-16 where most characters contain one and with --null-data where they end
+        // records.
+        let binary = self.config.binary_files != BinaryFiles::Text
+            && !self.config.null_data
+            && !file_encoding.is_some_and(encoding::is_utf16)
+            && reader.fill_buf()?.contains(&0);
+        sink.begin(path)?;
+        if binary && self.config.binary_files == BinaryFiles::WithoutMatch {
+            // -I assumes binary files never match, which -c, -L and --json still report.
+            let stats = FileStats::default();
+            sink.finish(path, &stats)?;
+            return Ok(stats);
+        }
+
+        let mut lines = Lines {
+            reader,
+            transcoder,
+            terminator,
+            line_number: 0,
+            // The BOM is part of the input, so the first line starts after it.
+            offset: bom_length as u64,
+        };
+        let mut reporter = Reporter::new(self.config, sink, path, binary);
+        let mut stats = FileStats::default();
+        if self.config.multiline {
+            self.search_multiline(&mut lines, &mut reporter, &mut stats)?;
+        } else {
+            self.search_lines(&mut lines, &mut reporter, &mut stats)?;
+        }
+        stats.bytes_searched = lines.offset;
+
+        sink.finish(path, &stats)?;
+        Ok(stats)
+    }
+
+    /// Matches each line on its own.
+    fn search_lines<S: Sink>(
+        &self,
+        lines: &mut Lines<'_>,
+        reporter: &mut Reporter<'_, S>,
+        stats: &mut FileStats,
+    ) -> io::Result<()> {
+        let mut line = Vec::new();
+        while !reporter.finished() {
+            let Some((line_number, offset)) = lines.next(&mut line)? else {
+                return Ok(());
+            };
+            let selected = !reporter.reached_max_count()
+                && self.matcher.is_match(&line) != self.config.invert_match;
+            if !selected {
+                if !reporter.unselected(line_number, offset, &line)? {
+                    return Ok(());
+                }
+                continue;
+            }
+
+            // One selected line decides -l, -L and -q, so its matches are never needed.
+            let ranges = if self.config.invert_match || reporter.stops_at_first_match() {
+                Vec::new()
+            } else {
+                self.matcher.find_matches(&line)
+            };
+            stats.matches += ranges.len() as u64;
+            let replacements = if self.config.count_only {
+                None
+            } else {
+                self.matcher.replacements(&line, &ranges)
+            };
+            let selected = SelectedLine {
+                line_number,
+                offset,
+                line: &line,
+                original: lines.original(),
+                matches: &ranges,
+                replacements: replacements.as_deref(),
+                counted: 1,
+            };
+            if !reporter.selected(stats, selected)? {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// Matches a window of lines at a time for `-U`, so that matches can span lines.
+    ///
+    /// Matches starting in the first half of the window are reported, along with every line
+    /// they cover, and those lines are dropped before the window is refilled. Memory stays
+    /// bounded by `MULTILINE_WINDOW`, which limits a match to about half of it.
+    fn search_multiline<S: Sink>(
+        &self,
+        lines: &mut Lines<'_>,
+        reporter: &mut Reporter<'_, S>,
+        stats: &mut FileStats,
+    ) -> io::Result<()> {
+        let mut window = Window::default();
+        let mut line = Vec::new();
+        let mut end_of_input = false;
+        let mut found = Vec::new();
+        loop {
+            while !end_of_input && window.text.len() < MULTILINE_WINDOW {
+                match lines.next(&mut line)? {
+                    Some((line_number, offset)) => {
+                        window.push(line_number, offset, &line, lines.terminator, lines.original())
+                    }
+                    None => end_of_input = true,
+                }
+            }
+            if window.lines.is_empty() {
+                return Ok(());
+            }
+
+            // Matches that start later are searched again once more lines follow them.
+            let mut cutoff = if end_of_input {
+                window.text.len()
+            } else {
+                window.line_end(window.text.len() / 2)
+            };
+            found.clear();
+            let mut start = 0;
+            while start <= window.text.len() {
+                let Some(range) = self.matcher.find_at(&window.text, start) else {
+                    break;
+                };
+                if range.start >= cutoff {
+                    break;
+                }
+                // Whole lines are reported, so a match ending mid-line takes the rest of the
+                // line with it, along with any match starting there.
+                cutoff = cutoff.max(window.line_end(range.end.max(range.start + 1) - 1));
+                start = if range.is_empty() { range.end + 1 } else { range.end };
+                found.push(range);
+            }
+
+            if !self.report_window(&window, cutoff, &found, reporter, stats)? {
+                return Ok(());
+            }
+            window.consume(cutoff);
+        }
+    }
+
+    /// Reports the lines of `window` before `cutoff`, selecting those that `found` touches.
+    fn report_window<S: Sink>(
+        &self,
+        window: &Window,
+        cutoff: usize,
+        found: &[Range<usize>],
+        reporter: &mut Reporter<'_, S>,
+        stats: &mut FileStats,
+    ) -> io::Result<bool> {
+        let mut next = 0;
+        // Where the first match past -m starts; it and the matches after it are dropped.
+        let mut unreported = usize::MAX;
+        let mut ranges = Vec::new();
+        let mut replacements = Vec::new();
+        for (i, &(line_number, offset, start)) in window.lines.iter().enumerate() {
+            if start >= cutoff {
+                break;
+            }
+            let end = window.lines.get(i + 1).map_or(window.text.len(), |next| next.2);
+            let line = &window.text[start..end - 1];
+
+            // Empty matches still select the line they are on.
+            while found.get(next).is_some_and(|range| range.end.max(range.start + 1) <= start) {
+                next += 1;
+            }
+            // -m counts matches here, and the lines of the last one are all reported.
+            let carried = found
+                .get(next)
+                .is_some_and(|range| range.start < start && range.start < unreported);
+            if reporter.finished() && !carried {
+                break;
+            }
+            let max_count_reached = reporter.reached_max_count();
+            ranges.clear();
+            replacements.clear();
+            let mut touched = false;
+            let mut starting = 0;
+            for range in found[next..].iter().take_while(|range| range.start < end) {
+                let starts_here = range.start >= start;
+                if starts_here && max_count_reached {
+                    unreported = unreported.min(range.start);
+                }
+                if range.start >= unreported {
+                    break;
+                }
+                touched = true;
+                let clipped = range.start.max(start) - start..range.end.min(end - 1) - start;
+                let replacement = if !starts_here {
+                    // The match was replaced where it started, so its tail disappears.
+                    Some(Vec::new())
+                } else {
+                    starting += 1;
+                    self.matcher
+                        .replacements(&window.text, std::slice::from_ref(range))
+                        .and_then(|mut replacements| replacements.pop())
+                };
+                if !clipped.is_empty() || (starts_here && replacement.is_some()) {
+                    ranges.push(clipped);
+                    replacements.extend(replacement);
+                }
+            }
+
+            let keep_going = if touched != self.config.invert_match
+                && !(self.config.invert_match && max_count_reached)
+            {
+                if self.config.invert_match {
+                    ranges.clear();
+                    replacements.clear();
+                } else {
+                    stats.matches += starting;
+                }
+                let has_replacements = self.config.replace.is_some() && !ranges.is_empty();
+                let selected = SelectedLine {
+                    line_number,
+                    offset,
+                    line,
+                    original: window.original(i),
+                    matches: &ranges,
+                    replacements: has_replacements.then_some(&replacements[..]),
+                    counted: if self.config.invert_match { 1 } else { starting as usize },
+                };
+                reporter.selected(stats, selected)?
+            } else {
+                reporter.unselected(line_number, offset, line)?
+            };
+            if !keep_going {
+                return Ok(false);
+            }
+        }
+        Ok(!reporter.finished())
+    }
+}
+
+/// The bytes `-U` searches at once before sliding forward; a match spans at most about
+/// half of it.
+const MULTILINE_WINDOW: usize = 4 * BUFFER_SIZE;
+
+/// Lines read for `-U`, each followed by its terminator so that patterns can match across
+/// them.
+#[derive(Default)]
+struct Window {
+    text: Vec<u8>,
+    /// The number and input offset of each line, and where it starts in `text`.
+    lines: VecDeque<(usize, u64, usize)>,
+    /// Each line as read from the input and its encoding, when it was transcoded.
+    originals: VecDeque<Option<(&'static Encoding, Vec<u8>)>>,
+}
+
+impl Window {
+    fn push(
+        &mut self,
+        line_number: usize,
+        offset: u64,
+        line: &[u8],
+        terminator: u8,
+        original: Option<(&'static Encoding, &[u8])>,
+    ) {
+        self.lines.push_back((line_number, offset, self.text.len()));
+        self.originals.push_back(original.map(|(encoding, raw)| (encoding, raw.to_vec())));
+        self.text.extend_from_slice(line);
+        self.text.push(terminator);
+    }
+
+    /// The line at `index` as read from the input, when it was transcoded.
+    fn original(&self, index: usize) -> Option<(&'static Encoding, &[u8])> {
+        self.originals[index].as_ref().map(|(encoding, raw)| (*encoding, &raw[..]))
+    }
+
+    /// The end, after its terminator, of the line containing `position`.
+    fn line_end(&self, position: usize) -> usize {
+        let next = self.lines.partition_point(|&(_, _, start)| start <= position);
+        self.lines.get(next).map_or(self.text.len(), |line| line.2)
+    }
+
+    /// Drops the lines before `end`, which is the start of a line or the end of the text.
+    fn consume(&mut self, end: usize) {
+        self.text.drain(..end);
+        while self.lines.front().is_some_and(|line| line.2 < end) {
+            self.lines.pop_front();
+            self.originals.pop_front();
+        }
+        for line in &mut self.lines {
+            line.2 -= end;
+        }
+    }
+}
+
+/// Reads an input line by line, transcoding it first when it is not UTF-8.
+struct Lines<'r> {
+    reader: Box<dyn BufRead + 'r>,
+    transcoder: Option<Transcoder>,
+    /// `\n`, or NUL for `--null-data`.
+    terminator: u8,
+    line_number: usize,
+    /// The bytes read from the input so far.
+    offset: u64,
+}
+
+impl Lines<'_> {
+    /// Reads the next line into `line` without its terminator, returning its line number
+    /// and byte offset, or `None` at end of input.
+    fn next(&mut self, line: &mut Vec<u8>) -> io::Result<Option<(usize, u64)>> {
+        let read = match &mut self.transcoder {
+            Some(transcoder) => transcoder.read_line(&mut self.reader, line)?,
+            None => read_line(&mut self.reader, line, self.terminator)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        let offset = self.offset;
+        self.offset += read as u64;
+        self.line_number += 1;
+        Ok(Some((self.line_number, offset)))
+    }
+
+    /// The last line as read from the input and its encoding, when it was transcoded.
+    fn original(&self) -> Option<(&'static Encoding, &[u8])> {
+        self.transcoder.as_ref().map(|transcoder| (transcoder.encoding(), transcoder.raw_line()))
+    }
+}
+
+/// A selected line, with the ranges and replacements to report for it.
+struct SelectedLine<'l> {
+    line_number: usize,
+    offset: u64,
+    line: &'l [u8],
+    original: Option<(&'static Encoding, &'l [u8])>,
+    matches: &'l [Range<usize>],
+    replacements: Option<&'l [Vec<u8>]>,
+    /// How much of -m the line uses up: one line, or under -U the matches starting on it.
+    counted: usize,
+}
+
+/// Decides what reaches the sink for each line: the selected ones, the context around them
+/// and the breaks between groups, and when the input needs no more reading.
+struct Reporter<'s, S: Sink> {
+    config: &'s Config,
+    sink: &'s mut S,
+    path: &'s Path,
+    binary: bool,
+    context: bool,
+    before: VecDeque<(usize, u64, Vec<u8>)>,
+    after_remaining: usize,
+    last_printed: Option<usize>,
+    /// Selected lines left before -m stops the search.
+    remaining: Option<usize>,
+}
+
+impl<'s, S: Sink> Reporter<'s, S> {
+    fn new(config: &'s Config, sink: &'s mut S, path: &'s Path, binary: bool) -> Self {
+        Self {
+            config,
+            sink,
+            path,
+            binary,
+            context: config.has_context() && !config.count_only && config.list_files.is_none(),
+            before: VecDeque::new(),
+            after_remaining: 0,
+            last_printed: None,
+            remaining: config.max_count,
+        }
+    }
+
+    /// Whether -l, -L or -q only need to know that a line is selected.
+    fn stops_at_first_match(&self) -> bool {
+        self.config.list_files.is_some() || self.config.quiet
+    }
+
+    /// After -m NUM selected lines, only the trailing context is left to report.
+    fn reached_max_count(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    fn finished(&self) -> bool {
+        self.reached_max_count() && self.after_remaining == 0
+    }
+
+    /// Reports a selected line, returning whether the search should go on.
+    fn selected(&mut self, stats: &mut FileStats, line: SelectedLine<'_>) -> io::Result<bool> {
+        stats.matched_lines += 1;
+        if self.stops_at_first_match() {
+            return Ok(false);
+        }
+        self.remaining = self.remaining.map(|count| count.saturating_sub(line.counted));
+        if self.config.count_only {
+            return Ok(true);
+        }
+        if self.binary {
+            self.sink.binary_match(self.path)?;
+            return Ok(false);
+        }
+
+        for (line_number, offset, before) in std::mem::take(&mut self.before) {
+            self.start_group(line_number)?;
+            let context = SinkContext { line_number, offset, line: &before };
+            self.sink.context(self.path, &context)?;
+        }
+        if self.context {
+            self.start_group(line.line_number)?;
+        }
+        let selected = SinkMatch {
+            line_number: line.line_number,
+            offset: line.offset,
+            line: line.line,
+            matches: line.matches,
+            replacements: line.replacements,
+            original: line.original,
+            origins: OnceCell::new(),
+        };
+        if !self.sink.matched(self.path, &selected)? {
+            return Ok(false);
+        }
+        self.after_remaining = self.config.after_context;
+        Ok(true)
+    }
+
+    /// Reports a line that was not selected, as trailing context or kept for leading context.
+    fn unselected(&mut self, line_number: usize, offset: u64, line: &[u8]) -> io::Result<bool> {
+        if self.after_remaining > 0 {
+            self.after_remaining -= 1;
+            self.last_printed = Some(line_number);
+            return self.sink.context(self.path, &SinkContext { line_number, offset, line });
+        }
+        if self.context && self.config.before_context > 0 {
+            // Reuse the oldest line's allocation once the buffer is full.
+            let mut buffered = if self.before.len() == self.config.before_context {
+                self.before.pop_front().map(|(_, _, buffered)| buffered).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            buffered.clear();
+            buffered.extend_from_slice(line);
+            self.before.push_back((line_number, offset, buffered));
+        }
+        Ok(true)
+    }
+
+    /// Separates a new group of output lines from the previous one unless they are adjacent.
+    fn start_group(&mut self, line_number: usize) -> io::Result<()> {
+        if self.last_printed.is_none_or(|last| line_number > last + 1) {
+            self.sink.context_break()?;
+        }
+        self.last_printed = Some(line_number);
+        Ok(())
+    }
+}
+
+/// Reads the next line into `line` without its terminator, returning the number of bytes
+/// consumed from the input, or 0 at end of input.
+///
+/// The buffer is reused between lines so searching never allocates per line.
+fn read_line(reader: &mut dyn BufRead, line: &mut Vec<u8>, terminator: u8) -> io::Result<usize> {
+    line.clear();
+    let read = reader.read_until(terminator, line)?;
+    encoding::strip_terminator(line, terminator);
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use tempfile::NamedTempFile;
+    use crate::cli::{ColorChoice, ColumnUnit, ListFiles};
+    use crate::printer::Printer;
+
+    fn create_test_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "{}", content).unwrap();
+        file
+    }
+
+    /// Collects selected lines, stopping after the first two.
+    #[derive(Default)]
+    struct FirstTwo(Vec<(usize, String)>);
+
+    impl Sink for FirstTwo {
+        fn matched(&mut self, _path: &Path, line: &SinkMatch<'_>) -> io::Result<bool> {
+            let text = String::from_utf8_lossy(line.line).into_owned();
+            self.0.push((line.line_number, text));
+            Ok(self.0.len() < 2)
+        }
+    }
+
+    #[test]
+    fn test_search_reader_with_custom_sink() {
+        let config = Config::builder().pattern("hit").build().unwrap();
+        let searcher = Searcher::new(&config).unwrap();
+
+        let mut sink = FirstTwo::default();
+        let input = &b"hit 1\nmiss\nhit 2\nhit 3\n"[..];
+        let stats = searcher.search_reader("input", input, &mut sink).unwrap();
+        assert_eq!(sink.0, vec![(1, "hit 1".to_string()), (3, "hit 2".to_string())]);
+        assert_eq!(stats.matched_lines, 2);
+    }
+
+    #[test]
+    fn test_search_non_utf8_input() {
+        let config = Config {
+            patterns: vec!["caf".to_string()],
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"caf\xe9 au lait\r\n\xff\xfe\nno match\ncaf\xc3\xa9\n").unwrap();
+
+        let searcher = Searcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, Vec::new());
+        let stats = searcher.search_path(file.path(), &mut printer).unwrap();
+
+        assert_eq!(stats, FileStats { matched_lines: 2, matches: 2, bytes_searched: 32 });
+        assert_eq!(
+            String::from_utf8(printer.into_inner()).unwrap(),
+            "caf\u{fffd} au lait\ncaf\u{e9}\n"
+        );
+    }
+
+    #[test]
+    fn test_search_utf16_keeps_original_offsets() {
+        let config = Config {
+            patterns: vec!["caf\u{e9}".to_string()],
+            line_numbers: true,
+            byte_offset: true,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"\xff\xfe").unwrap();
+        for unit in "tea\ncaf\u{e9}\n".encode_utf16() {
+            file.write_all(&unit.to_le_bytes()).unwrap();
+        }
+        let searcher = Searcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, Vec::new());
+
+        let stats = searcher.search_path(file.path(), &mut printer).unwrap();
+        assert_eq!(stats.bytes_searched, 20);
+        assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "2:10:caf\u{e9}\n");
+    }
+
+    #[test]
+    fn test_search_shift_jis_offsets_count_input_bytes() {
+        // 0x81 starts a two-byte character, so alone it decodes to U+FFFD, three bytes long.
+        let input = &b"\x93\xfa \x81 hit\n"[..];
+        for multiline in [false, true] {
+            let config = Config {
+                patterns: vec!["hit".to_string()],
+                encoding: Some(encoding_rs::SHIFT_JIS),
+                only_matching: true,
+                byte_offset: true,
+                column: Some(ColumnUnit::Bytes),
+                multiline,
+                color: ColorChoice::Never,
+                ..Config::new()
+            };
+            let searcher = Searcher::new(&config).unwrap();
+            let mut printer = Printer::new(&config, Vec::new());
+            searcher.search_reader("-", input, &mut printer).unwrap();
+            assert_eq!(String::from_utf8(printer.into_inner()).unwrap(), "6:5:hit\n");
+        }
+    }
+
+    #[test]
+    fn test_binary_without_match_reaches_the_sink() {
+        let config = Config {
+            patterns: vec!["hit".to_string()],
+            paths: vec![PathBuf::from("bin"), PathBuf::from("txt")],
+            binary_files: BinaryFiles::WithoutMatch,
+            count_only: true,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let input = &b"hit\0\n"[..];
+        assert_eq!(search_text_bytes(&config, "bin", input), "bin:0\n");
+
+        let list_files = Some(ListFiles::WithoutMatch);
+        let config = Config { count_only: false, list_files, ..config };
+        assert_eq!(search_text_bytes(&config, "bin", input), "bin\n");
+    }
+
+    fn search_text_bytes(config: &Config, path: &str, input: &[u8]) -> String {
+        let searcher = Searcher::new(config).unwrap();
+        let mut printer = Printer::new(config, Vec::new());
+        let stats = searcher.search_reader(path, input, &mut printer).unwrap();
+        assert_eq!(stats, FileStats::default());
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_list_files_stops_at_first_match() {
+        let config = Config {
+            patterns: vec!["hit".to_string()],
+            list_files: Some(ListFiles::WithMatches),
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let file = create_test_file("miss\nhit\nhit again");
+        let searcher = Searcher::new(&config).unwrap();
+
+        let mut printer = Printer::new(&config, Vec::new());
+        let stats = searcher.search_path(file.path(), &mut printer).unwrap();
+        assert_eq!(stats, FileStats { matched_lines: 1, matches: 0, bytes_searched: 9 });
+        assert_eq!(printer.into_inner(), format!("{}\n", file.path().display()).as_bytes());
+
+        let config = Config { list_files: Some(ListFiles::WithoutMatch), ..config };
+        let searcher = Searcher::new(&config).unwrap();
+        let mut printer = Printer::new(&config, Vec::new());
+        searcher.search_path(file.path(), &mut printer).unwrap();
+        assert!(printer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_max_count_keeps_trailing_context() {
+        let config = Config {
+            patterns: vec!["hit".to_string()],
+            max_count: Some(1),
+            after_context: 1,
+            color: ColorChoice::Never,
+            ..Config::new()
+        };
+        let file = create_test_file("hit 1\nhit 2\nhit 3");
+        let searcher = Searcher::new(&config).unwrap();
+
+        let mut printer = Printer::new(&config, Vec::new());
+        let stats = searcher.search_path(file.path(), &mut printer).unwrap();
+        assert_eq!(stats.matched_lines, 1);
+        assert_eq!(printer.into_inner(), b"hit 1\nhit 2\n");
+    }
+
+    fn search_text(config: &Config, text: &str) -> (String, FileStats) {
+        let searcher = Searcher::new(config).unwrap();
+        let mut printer = Printer::new(config, Vec::new());
+        let stats = searcher.search_reader("-", text.as_bytes(), &mut printer).unwrap();
+        (String::from_utf8(printer.into_inner()).unwrap(), stats)
+    }
+
+    #[test]
+    fn test_multiline_prints_every_line_of_a_match() {
+        let config = Config::builder()
+            .pattern(r"fn \w+\([^)]*\)")
+            .multiline(true)
+            .line_numbers(true)
+            .build()
+            .unwrap();
+        let text = "use a;\nfn one(\n    x: u8,\n) {}\nfn two() {}\nlast\n";
+
+        let (output, stats) = search_text(&config, text);
+        assert_eq!(output, "2:fn one(\n3:    x: u8,\n4:) {}\n5:fn two() {}\n");
+        assert_eq!((stats.matched_lines, stats.matches), (4, 2));
+
+        let config = Config { invert_match: true, ..config };
+        assert_eq!(search_text(&config, text).0, "1:use a;\n6:last\n");
+    }
+
+    #[test]
+    fn test_multiline_dotall() {
+        let text = "start\nmiddle\nend\n";
+        let config = Config::builder().pattern("start.*end").multiline(true).build().unwrap();
+        assert_eq!(search_text(&config, text).1.matched_lines, 0);
+
+        let config = Config { multiline_dotall: true, ..config };
+        assert_eq!(search_text(&config, text).0, text);
+    }
+
+    #[test]
+    fn test_multiline_max_count_finishes_the_match() {
+        let text = "a\nfoo(\nx) foo(\ny)\nfoo()\n";
+        let config = Config::builder()
+            .pattern(r"foo\([^)]*\)")
+            .multiline(true)
+            .max_count(Some(1))
+            .line_numbers(true)
+            .build()
+            .unwrap();
+        let (output, stats) = search_text(&config, text);
+        assert_eq!(output, "2:foo(\n3:x) foo(\n");
+        assert_eq!(stats.matches, 1);
+
+        let config = Config { max_count: Some(2), ..config };
+        assert_eq!(search_text(&config, text).0, "2:foo(\n3:x) foo(\n4:y)\n");
+    }
+
+    #[test]
+    fn test_multiline_replace() {
+        let config = Config::builder()
+            .pattern(r"\((?s:.*?)\)")
+            .replace("()")
+            .multiline(true)
+            .build()
+            .unwrap();
+        assert_eq!(search_text(&config, "call(\n  x) + 1\n").0, "call()\n + 1\n");
+    }
+
+    #[test]
+    fn test_null_data_records() {
+        // Records hold newlines and are never treated as binary for their NULs.
+        let text = "one\nhit\0two\0hit\r\0";
+        let config = Config::builder().pattern("^hit").null_data(true).build().unwrap();
+        let (output, stats) = search_text(&config, text);
+        assert_eq!(output, "hit\r\0");
+        assert_eq!(stats.bytes_searched, text.len() as u64);
+
+        // With -U, `^` and `$` anchor at NULs rather than newlines.
+        let config = Config { multiline: true, ..config };
+        assert_eq!(search_text(&config, text).0, "hit\r\0");
+        let config = Config { patterns: vec![r"hit\x00two$".to_string()], ..config };
+        assert_eq!(search_text(&config, text).0, "one\nhit\0two\0");
+    }
+
+    #[test]
+    fn test_multiline_window_slides() {
+        // Pairs straddle every point where the window could be cut.
+        let mut text = String::new();
+        let mut pairs = 0;
+        while text.len() < 3 * MULTILINE_WINDOW {
+            if text.len() % 1000 < 40 {
+                text.push_str("begin\nend\n");
+                pairs += 1;
+            }
+            text.push_str("filler line of thirty bytes..\n");
+        }
+        let config = Config::builder().pattern(r"begin\nend").multiline(true).build().unwrap();
+
+        let (_, stats) = search_text(&config, &text);
+        assert_eq!(stats.matches, pairs);
+        assert_eq!(stats.matched_lines, 2 * pairs);
+        assert_eq!(stats.bytes_searched, text.len() as u64);
+    }
+}
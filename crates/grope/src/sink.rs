@@ -0,0 +1,120 @@
+use std::cell::OnceCell;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use encoding_rs::Encoding;
+use crate::cli::ColumnUnit;
+use crate::encoding;
+use crate::stats::FileStats;
+
+/// Receives the results of a search from a `Searcher`, one input at a time.
+///
+/// Only `matched` is required; the other callbacks default to doing nothing. Returning
+/// `Ok(false)` from `matched` or `context` stops searching the current input, and errors
+/// are passed back to the caller of the `Searcher`.
+pub trait Sink {
+    /// Called before the first line of `path` is searched.
+    fn begin(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called for each selected line.
+    fn matched(&mut self, path: &Path, line: &SinkMatch<'_>) -> io::Result<bool>;
+
+    /// Called for each line of context around the selected ones (`-A`, `-B`, `-C`).
+    fn context(&mut self, _path: &Path, _line: &SinkContext<'_>) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// Called before a group of context and selected lines that does not directly follow
+    /// the previous group.
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called instead of `matched` when a binary input has a selected line; the search of
+    /// that input stops there.
+    fn binary_match(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once `path` has been searched, with the counts gathered along the way.
+    fn finish(&mut self, _path: &Path, _stats: &FileStats) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A selected line, without its line terminator.
+#[derive(Debug, Clone)]
+pub struct SinkMatch<'a> {
+    pub line_number: usize,
+    /// The byte offset of the line in the input.
+    pub offset: u64,
+    /// The line, transcoded to UTF-8 if the input was in another encoding.
+    pub line: &'a [u8],
+    /// The byte ranges of every match in `line`, empty for lines selected by `-v`.
+    pub matches: &'a [Range<usize>],
+    /// The `--replace` template expanded for each match, when one was given.
+    pub replacements: Option<&'a [Vec<u8>]>,
+    /// The line as read from the input and its encoding, when it was transcoded.
+    pub(crate) original: Option<(&'static Encoding, &'a [u8])>,
+    /// Where the characters of `line` start in `original`, found the first time a match
+    /// offset is asked for; see `encoding::origins`.
+    pub(crate) origins: OnceCell<Vec<(usize, usize)>>,
+}
+
+impl<'a> SinkMatch<'a> {
+    pub fn new(
+        line_number: usize,
+        offset: u64,
+        line: &'a [u8],
+        matches: &'a [Range<usize>],
+    ) -> Self {
+        Self {
+            line_number,
+            offset,
+            line,
+            matches,
+            replacements: None,
+            original: None,
+            origins: OnceCell::new(),
+        }
+    }
+
+    /// Attaches the expanded `--replace` template for each of `matches`.
+    pub fn with_replacements(self, replacements: &'a [Vec<u8>]) -> Self {
+        Self { replacements: Some(replacements), ..self }
+    }
+
+    /// The byte offset in the input of the match at `range`, which differs from
+    /// `offset + range.start` when the input was transcoded.
+    pub fn match_offset(&self, range: &Range<usize>) -> u64 {
+        let start = match self.original {
+            Some((encoding, raw)) => {
+                let origins = self.origins.get_or_init(|| encoding::origins(encoding, raw));
+                encoding::original_position(origins, range.start)
+            }
+            None => range.start,
+        };
+        self.offset + start as u64
+    }
+
+    /// The 1-based column of the match at `range`. Bytes are counted in the input's
+    /// encoding, like `match_offset`, and characters in the line as searched.
+    pub fn match_column(&self, range: &Range<usize>, unit: ColumnUnit) -> usize {
+        let before = match unit {
+            ColumnUnit::Bytes => (self.match_offset(range) - self.offset) as usize,
+            ColumnUnit::Chars => String::from_utf8_lossy(&self.line[..range.start]).chars().count(),
+        };
+        before + 1
+    }
+}
+
+/// A context line, without its line terminator.
+#[derive(Debug, Clone)]
+pub struct SinkContext<'a> {
+    pub line_number: usize,
+    /// The byte offset of the line in the input.
+    pub offset: u64,
+    pub line: &'a [u8],
+}
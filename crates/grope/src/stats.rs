@@ -0,0 +1,31 @@
+/// Counters for a single searched file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStats {
+    pub matched_lines: u64,
+    pub matches: u64,
+    pub bytes_searched: u64,
+}
+
+/// Totals across every file searched in a run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub searches: u64,
+    pub searches_with_match: u64,
+    pub matched_lines: u64,
+    pub matches: u64,
+    pub bytes_searched: u64,
+    /// Files and directories that could not be read.
+    pub errors: u64,
+}
+
+impl Stats {
+    pub fn add(&mut self, file: &FileStats) {
+        self.searches += 1;
+        if file.matched_lines > 0 {
+            self.searches_with_match += 1;
+        }
+        self.matched_lines += file.matched_lines;
+        self.matches += file.matches;
+        self.bytes_searched += file.bytes_searched;
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use crate::error::GropeError;
+
+/// The file types `-t` and `-T` know about without `--type-add`, and their file name globs.
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css", "*.scss"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// Maps file type names to the globs that select them, for `-t`, `-T` and `--type-list`.
+#[derive(Debug)]
+pub struct FileTypes {
+    types: BTreeMap<String, Vec<String>>,
+}
+
+impl FileTypes {
+    /// Starts from the built-in table and applies each `--type-add NAME:GLOB` in order,
+    /// which either defines a new type or adds a glob to an existing one.
+    pub fn new(additions: &[String]) -> Result<Self, GropeError> {
+        let mut types: BTreeMap<String, Vec<String>> = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| (name.to_string(), globs.iter().map(|g| g.to_string()).collect()))
+            .collect();
+        for addition in additions {
+            let invalid = || GropeError::InvalidValue("--type-add".to_string(), addition.clone());
+            let (name, glob) = addition.split_once(':').ok_or_else(invalid)?;
+            if name.is_empty() || glob.is_empty() {
+                return Err(invalid());
+            }
+            types.entry(name.to_string()).or_default().push(glob.to_string());
+        }
+        Ok(Self { types })
+    }
+
+    /// Builds one set from the globs of every type in `names`; `flag` names the option they
+    /// came from when one is unknown.
+    pub fn globs(&self, names: &[String], flag: &str) -> Result<GlobSet, GropeError> {
+        let mut builder = GlobSetBuilder::new();
+        for name in names {
+            let globs = self
+                .types
+                .get(name)
+                .ok_or_else(|| GropeError::InvalidValue(flag.to_string(), name.clone()))?;
+            for glob in globs {
+                builder.add(Glob::new(glob)?);
+            }
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Prints every type with its globs, one per line, for `--type-list`.
+    pub fn write_list<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (name, globs) in &self.types {
+            writeln!(out, "{}: {}", name, globs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_globs() {
+        let types = FileTypes::new(&[]).unwrap();
+        let rust = types.globs(&["rust".to_string()], "--type").unwrap();
+        assert!(rust.is_match("lib.rs"));
+        assert!(!rust.is_match("lib.rs.bak"));
+
+        let make = types.globs(&["make".to_string(), "c".to_string()], "--type").unwrap();
+        assert!(make.is_match("Makefile"));
+        assert!(make.is_match("main.h"));
+
+        let err = types.globs(&["cobol".to_string()], "--type-not").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value for --type-not: cobol");
+    }
+
+    #[test]
+    fn test_type_add_and_list() {
+        let additions = ["proto:*.proto".to_string(), "rust:*.rs.in".to_string()];
+        let types = FileTypes::new(&additions).unwrap();
+        assert!(types.globs(&["proto".to_string()], "--type").unwrap().is_match("a.proto"));
+
+        let mut list = Vec::new();
+        types.write_list(&mut list).unwrap();
+        let list = String::from_utf8(list).unwrap();
+        assert!(list.starts_with("c: *.c, *.h\n"));
+        assert!(list.contains("\nproto: *.proto\npy: *.py, *.pyi\n"));
+        assert!(list.contains("\nrust: *.rs, *.rs.in\n"));
+
+        for addition in ["proto", ":*.proto", "proto:"] {
+            let result = FileTypes::new(&[addition.to_string()]);
+            assert!(matches!(result, Err(GropeError::InvalidValue(_, _))));
+        }
+    }
+}
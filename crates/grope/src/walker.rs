@@ -0,0 +1,264 @@
+use std::fs::{self, Metadata};
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use crate::cli::{Config, SymlinkPolicy};
+use crate::error::GropeError;
+use crate::types::FileTypes;
+
+/// Walks directory trees for `-r`/`-R`, yielding the regular files that should be searched.
+pub struct Walker<'a> {
+    config: &'a Config,
+    include: GlobSet,
+    exclude: GlobSet,
+    exclude_dir: GlobSet,
+    /// The globs of the `-t` types, and of the `-T` types.
+    types: GlobSet,
+    types_not: GlobSet,
+}
+
+impl<'a> Walker<'a> {
+    pub fn new(config: &'a Config) -> Result<Self, GropeError> {
+        let file_types = FileTypes::new(&config.type_add)?;
+        Ok(Self {
+            config,
+            include: build_globs(&config.include)?,
+            exclude: build_globs(&config.exclude)?,
+            exclude_dir: build_globs(&config.exclude_dir)?,
+            types: file_types.globs(&config.types, "--type")?,
+            types_not: file_types.globs(&config.types_not, "--type-not")?,
+        })
+    }
+
+    /// Calls `visit` for every file under `root`, in sorted order within each directory.
+    ///
+    /// Unreadable directories and dangling links are passed to `visit` as errors and
+    /// skipped. The walk stops as soon as `visit` breaks.
+    pub fn walk<F>(&self, root: &Path, visit: &mut F) -> ControlFlow<()>
+    where
+        F: FnMut(Result<&Path, GropeError>) -> ControlFlow<()>,
+    {
+        let metadata = if self.config.symlinks == SymlinkPolicy::Never {
+            fs::symlink_metadata(root)
+        } else {
+            fs::metadata(root)
+        };
+
+        match metadata {
+            Ok(metadata) if metadata.is_dir() => {
+                let mut ancestors = Vec::new();
+                self.walk_dir(root, 0, &mut ancestors, visit)
+            }
+            Ok(metadata) if metadata.is_file() => visit(Ok(root)),
+            Ok(_) => ControlFlow::Continue(()),
+            Err(e) => visit(Err(GropeError::File(root.to_path_buf(), e))),
+        }
+    }
+
+    fn walk_dir<F>(
+        &self,
+        dir: &Path,
+        depth: usize,
+        ancestors: &mut Vec<PathBuf>,
+        visit: &mut F,
+    ) -> ControlFlow<()>
+    where
+        F: FnMut(Result<&Path, GropeError>) -> ControlFlow<()>,
+    {
+        if self.config.max_depth.is_some_and(|max| depth >= max) {
+            return ControlFlow::Continue(());
+        }
+
+        // Following links can lead back into a directory we are already inside.
+        let canonical = match fs::canonicalize(dir) {
+            Ok(canonical) => canonical,
+            Err(e) => return visit(Err(GropeError::File(dir.to_path_buf(), e))),
+        };
+        if ancestors.contains(&canonical) {
+            let e = io::Error::other("recursive directory loop");
+            return visit(Err(GropeError::File(dir.to_path_buf(), e)));
+        }
+
+        let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect(),
+            Err(e) => return visit(Err(GropeError::File(dir.to_path_buf(), e))),
+        };
+        entries.sort();
+
+        ancestors.push(canonical);
+        for path in entries {
+            let metadata = match self.entry_metadata(&path) {
+                Ok(Some(metadata)) => metadata,
+                Ok(None) => continue,
+                Err(e) => {
+                    visit(Err(GropeError::File(path, e)))?;
+                    continue;
+                }
+            };
+            let name = path.file_name().unwrap_or_default();
+
+            if metadata.is_dir() {
+                if !self.exclude_dir.is_match(name) {
+                    self.walk_dir(&path, depth + 1, ancestors, visit)?;
+                }
+            } else if metadata.is_file() && self.is_included(Path::new(name)) {
+                visit(Ok(&path))?;
+            }
+        }
+        ancestors.pop();
+
+        ControlFlow::Continue(())
+    }
+
+    /// Resolves an entry's metadata according to the symlink policy; `None` skips the entry.
+    fn entry_metadata(&self, path: &Path) -> io::Result<Option<Metadata>> {
+        let metadata = fs::symlink_metadata(path)?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(Some(metadata));
+        }
+        if self.config.symlinks != SymlinkPolicy::Always {
+            return Ok(None);
+        }
+        fs::metadata(path).map(Some)
+    }
+
+    /// Checks a file's name against the globs and file types, before it is ever opened.
+    fn is_included(&self, name: &Path) -> bool {
+        (self.include.is_empty() || self.include.is_match(name))
+            && !self.exclude.is_match(name)
+            && (self.config.types.is_empty() || self.types.is_match(name))
+            && !self.types_not.is_match(name)
+    }
+}
+
+fn build_globs(patterns: &[String]) -> Result<GlobSet, GropeError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_directory() -> TempDir {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::create_dir(temp_dir.path().join("target")).unwrap();
+        fs::write(temp_dir.path().join("top.txt"), "x").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "x").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs.bak"), "x").unwrap();
+        fs::write(temp_dir.path().join("src/nested/deep.rs"), "x").unwrap();
+        fs::write(temp_dir.path().join("target/out.rs"), "x").unwrap();
+        temp_dir
+    }
+
+    fn walk(config: &Config, root: &Path) -> Vec<PathBuf> {
+        let walker = Walker::new(config).unwrap();
+        let mut files = Vec::new();
+        let _ = walker.walk(root, &mut |path| {
+            if let Ok(path) = path {
+                files.push(path.strip_prefix(root).unwrap().to_path_buf());
+            }
+            ControlFlow::Continue(())
+        });
+        files
+    }
+
+    #[test]
+    fn test_walk_all_files() {
+        let temp_dir = setup_test_directory();
+        let config = Config {
+            recursive: true,
+            ..Config::new()
+        };
+
+        let files = walk(&config, temp_dir.path());
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("src/lib.rs.bak"),
+                PathBuf::from("src/nested/deep.rs"),
+                PathBuf::from("target/out.rs"),
+                PathBuf::from("top.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk_with_filters() {
+        let temp_dir = setup_test_directory();
+        let config = Config {
+            recursive: true,
+            include: vec!["*.rs".to_string(), "*.bak".to_string()],
+            exclude: vec!["*.bak".to_string()],
+            exclude_dir: vec!["target".to_string()],
+            max_depth: Some(2),
+            ..Config::new()
+        };
+
+        let files = walk(&config, temp_dir.path());
+        assert_eq!(files, vec![PathBuf::from("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_walk_with_types() {
+        let temp_dir = setup_test_directory();
+        let config = Config {
+            recursive: true,
+            types: vec!["rust".to_string(), "bak".to_string()],
+            types_not: vec!["txt".to_string()],
+            type_add: vec!["bak:*.bak".to_string()],
+            exclude_dir: vec!["target".to_string()],
+            ..Config::new()
+        };
+        let files = walk(&config, temp_dir.path());
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("src/lib.rs"),
+                PathBuf::from("src/lib.rs.bak"),
+                PathBuf::from("src/nested/deep.rs"),
+            ]
+        );
+
+        let config = Config { types: vec![], types_not: vec!["rust".to_string()], ..config };
+        let files = walk(&config, temp_dir.path());
+        assert_eq!(files, vec![PathBuf::from("src/lib.rs.bak"), PathBuf::from("top.txt")]);
+
+        let config = Config { types: vec!["nope".to_string()], ..Config::new() };
+        assert!(matches!(Walker::new(&config), Err(GropeError::InvalidValue(_, _))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_symlink_policies() {
+        let temp_dir = setup_test_directory();
+        std::os::unix::fs::symlink(temp_dir.path().join("src"), temp_dir.path().join("link"))
+            .unwrap();
+        std::os::unix::fs::symlink(temp_dir.path(), temp_dir.path().join("src/loop")).unwrap();
+
+        let config = Config {
+            recursive: true,
+            exclude_dir: vec!["target".to_string()],
+            ..Config::new()
+        };
+        assert_eq!(walk(&config, temp_dir.path()).len(), 4);
+
+        let config = Config {
+            symlinks: SymlinkPolicy::Always,
+            ..config
+        };
+        let files = walk(&config, temp_dir.path());
+        assert!(files.contains(&PathBuf::from("link/nested/deep.rs")));
+        assert!(!files.iter().any(|path| path.starts_with("src/loop")));
+    }
+}
@@ -0,0 +1,389 @@
+use assert_cmd::Command;
+use tempfile::NamedTempFile;
+use std::io::Write;
+
+fn create_test_file(content: &str) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    write!(file, "{}", content).unwrap();
+    file
+}
+
+#[test]
+fn test_cli_context_groups() {
+    let file = create_test_file("a\nmatch 1\nb\nc\nd\ne\nmatch 2\nmatch 3\nf\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-n", "-B1", "-A1", "match"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("1-a\n2:match 1\n3-b\n--\n6-e\n7:match 2\n8:match 3\n9-f\n");
+}
+
+#[test]
+fn test_cli_overlapping_context_is_merged() {
+    let file = create_test_file("match\nx\nmatch\ny\nz\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-C", "1", "match"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("match\nx\nmatch\ny\n");
+}
+
+#[test]
+fn test_cli_color_always() {
+    let file = create_test_file("one foo two foo\nbar\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["--color=always", "-n", "foo"])
+        .arg(file.path())
+        .env_remove("GREP_COLORS")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "\x1b[32m\x1b[K1\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K",
+            "one \x1b[01;31m\x1b[Kfoo\x1b[m\x1b[K two \x1b[01;31m\x1b[Kfoo\x1b[m\x1b[K\n",
+        ));
+}
+
+#[test]
+fn test_cli_color_auto_is_plain_when_piped() {
+    let file = create_test_file("foo\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.arg("foo").arg(file.path()).assert().success().stdout("foo\n");
+}
+
+#[test]
+fn test_cli_parallel_output_keeps_input_order() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut expected = String::new();
+    for i in 0..40 {
+        let path = temp_dir.path().join(format!("file{:02}.txt", i));
+        std::fs::write(&path, format!("skip\nhit {}\nhit again\n", i)).unwrap();
+        expected.push_str(&format!("{}:2:hit {}\n", path.display(), i));
+        expected.push_str(&format!("{}:3:hit again\n", path.display()));
+    }
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-j", "8", "-n", "-r", "hit"])
+        .arg(temp_dir.path())
+        .assert()
+        .success()
+        .stdout(expected);
+}
+
+#[test]
+fn test_cli_binary_files() {
+    let file = create_test_file("text match\n\0\x01\x02 match\n");
+    let notice = format!("Binary file {} matches\n", file.path().display());
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.arg("match").arg(file.path()).assert().success().stdout(notice);
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-c", "match"]).arg(file.path()).assert().success().stdout("2\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-a", "text"]).arg(file.path()).assert().success().stdout("text match\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-I", "match"]).arg(file.path()).assert().code(1).stdout("");
+}
+
+#[test]
+fn test_cli_json_output() {
+    let file = create_test_file("a:b\nnope\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    let output = cmd.args(["--json", "-A1", "b"]).arg(file.path()).output().unwrap();
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, vec!["begin", "match", "context", "end", "summary"]);
+    assert_eq!(events[1]["data"]["lines"]["text"], "a:b");
+    assert_eq!(events[1]["data"]["submatches"][0]["start"], 2);
+    assert_eq!(events[2]["data"]["absolute_offset"], 4);
+    assert_eq!(events[3]["data"]["stats"]["bytes_searched"], 9);
+    assert_eq!(events[4]["data"]["stats"]["searches_with_match"], 1);
+}
+
+#[test]
+fn test_cli_list_files() {
+    let hit = create_test_file("one hit\n");
+    let miss = create_test_file("nothing\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-l", "hit"])
+        .arg(hit.path())
+        .arg(miss.path())
+        .assert()
+        .success()
+        .stdout(format!("{}\n", hit.path().display()));
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-L", "hit"])
+        .arg(hit.path())
+        .arg(miss.path())
+        .assert()
+        .success()
+        .stdout(format!("{}\n", miss.path().display()));
+}
+
+#[test]
+fn test_cli_exit_status() {
+    let file = create_test_file("one hit\nanother hit\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.arg("hit").arg(file.path()).assert().code(0);
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.arg("miss").arg(file.path()).assert().code(1).stdout("");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-q", "hit"]).arg(file.path()).assert().code(0).stdout("");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-m", "1", "hit"]).arg(file.path()).assert().code(0).stdout("one hit\n");
+
+    // An unreadable file fails the run without hiding the other file's results.
+    let path = file.path().display();
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["hit", "does-not-exist"])
+        .arg(file.path())
+        .assert()
+        .code(2)
+        .stdout(format!("{}:one hit\n{}:another hit\n", path, path))
+        .stderr(predicates::str::starts_with("grope: does-not-exist: "));
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-s", "miss", "does-not-exist"]).assert().code(2).stderr("");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-q", "hit", "does-not-exist"]).arg(file.path()).assert().code(0);
+}
+
+#[test]
+fn test_cli_search_zip() {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(b"rotated hit\n").unwrap();
+    let gzip = create_test_file("");
+    std::fs::write(gzip.path(), encoder.finish().unwrap()).unwrap();
+    let corrupt = create_test_file("");
+    std::fs::write(corrupt.path(), b"\x1f\x8bnot really gzip").unwrap();
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-z", "-c", "hit"]).arg(gzip.path()).assert().success().stdout("1\n");
+
+    // A corrupt file is reported on its own, after the other files are searched.
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-z", "-l", "hit"])
+        .arg(corrupt.path())
+        .arg(gzip.path())
+        .assert()
+        .code(2)
+        .stdout(format!("{}\n", gzip.path().display()))
+        .stderr(predicates::str::contains(corrupt.path().display().to_string()));
+}
+
+#[test]
+fn test_cli_multiline() {
+    let file = create_test_file("#[test]\nfn check() {}\nfn other() {}\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-U", "-n", r"#\[test\]\nfn \w+"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("1:#[test]\n2:fn check() {}\n");
+}
+
+#[test]
+fn test_cli_file_types() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("main.rs"), "hit\n").unwrap();
+    std::fs::write(dir.path().join("notes.md"), "hit\n").unwrap();
+    std::fs::write(dir.path().join("data.proto"), "hit\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["-r", "-l", "-t", "rust", "--type", "proto", "--type-add=proto:*.proto", "hit"])
+        .assert()
+        .success()
+        .stdout("./data.proto\n./main.rs\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["--type-add", "proto:*.proto", "--type-list"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\nproto: *.proto\n"));
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-r", "-t", "cobol", "hit"]).assert().code(2);
+}
+
+#[test]
+fn test_cli_config_file() {
+    let config = create_test_file("# always number lines\n-n\n--max-count=nope\n");
+    let file = create_test_file("one hit\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.env("GROPE_CONFIG_PATH", config.path())
+        .args(["hit"])
+        .arg(file.path())
+        .assert()
+        .code(2)
+        .stderr(format!(
+            "Error: {}:3: Invalid value for --max-count: nope\n",
+            config.path().display()
+        ));
+
+    std::fs::write(config.path(), "-n\n").unwrap();
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.env("GROPE_CONFIG_PATH", config.path())
+        .args(["hit"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("1:one hit\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.env("GROPE_CONFIG_PATH", config.path())
+        .args(["--no-config", "hit"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one hit\n");
+
+    // Here --no-config is the pattern, so the config file still applies.
+    let file = create_test_file("a --no-config line\n");
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.env("GROPE_CONFIG_PATH", config.path())
+        .args(["-e", "--no-config"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("1:a --no-config line\n");
+}
+
+#[test]
+fn test_cli_stats() {
+    let first = create_test_file("hit hit\nmiss\n");
+    let second = create_test_file("miss\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-j2", "--stats", "hit"])
+        .arg(first.path())
+        .arg(second.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::starts_with(format!(
+            "{}:hit hit\n\n1 matched line\n2 matches\n2 files searched\n\
+             1 file contained matches\n18 bytes searched\n",
+            first.path().display()
+        )))
+        .stdout(predicates::str::ends_with(" seconds elapsed\n"));
+}
+
+#[test]
+fn test_cli_null_output_and_files_from() {
+    let first = create_test_file("hit\n");
+    let second = create_test_file("miss\n");
+    let list = format!("{}\n{}\n", first.path().display(), second.path().display());
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-l", "-Z", "--files-from", "-", "hit"])
+        .write_stdin(list)
+        .assert()
+        .success()
+        .stdout(format!("{}\0", first.path().display()));
+
+    let records = create_test_file("a\nhit\0miss\0");
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["--null-data", "hit"])
+        .arg(records.path())
+        .assert()
+        .success()
+        .stdout("a\nhit\0");
+}
+
+#[test]
+fn test_cli_column_and_vimgrep() {
+    let file = create_test_file("miss\n\u{e9}t\u{e9} hit hit\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["-n", "--column=chars", "hit"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("2:5:\u{e9}t\u{e9} hit hit\n");
+
+    let mut cmd = Command::cargo_bin("grope").unwrap();
+    cmd.args(["--vimgrep", "hit"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "{0}:2:7:\u{e9}t\u{e9} hit hit\n{0}:2:11:\u{e9}t\u{e9} hit hit\n",
+            file.path().display()
+        ));
+}
+
+#[test]
+fn test_cli_streams_matches_before_end_of_input() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_grope"))
+        .arg("hit")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Stdin stays open, like `tail -f`, so the line has to be printed before EOF.
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "miss\nhit").unwrap();
+
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line).unwrap();
+        let _ = tx.send(line);
+    });
+    let line = rx.recv_timeout(Duration::from_secs(10));
+    drop(stdin);
+    child.wait().unwrap();
+    assert_eq!(line.unwrap(), "hit\n");
+}
+
+#[test]
+fn test_cli_closed_stdout_is_not_an_error() {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    let file = create_test_file(&"hit\n".repeat(200_000));
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_grope"))
+        .args(["-j2", "hit"])
+        .arg(file.path())
+        .arg(file.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = [0; 4];
+    child.stdout.take().unwrap().read_exact(&mut first).unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(&first, &file.path().to_str().unwrap().as_bytes()[..4]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(output.status.code(), Some(0));
+}
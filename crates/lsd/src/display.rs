@@ -124,8 +124,10 @@ mod tests {
     #[test]
     fn test_list_directory_with_hidden() {
         let temp_dir = setup_test_directory();
-        let mut opts = Options::default();
-        opts.show_hidden = true;
+        let opts = Options {
+            show_hidden: true,
+            ..Default::default()
+        };
 
         let result = list_directory(temp_dir.path(), &opts, 0);
         assert!(result.is_ok());
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Options {
     pub show_hidden: bool,
     pub show_almost_all: bool,
@@ -9,20 +9,6 @@ pub struct Options {
     pub recursive: bool,
 }
 
-impl Default for Options {
-    fn default() -> Self {
-        Options {
-            show_hidden: false,
-            show_almost_all: false,
-            long_format: false,
-            human_readable: false,
-            sort_time: false,
-            reverse_sort: false,
-            recursive: false,
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,6 +1,6 @@
 use crate::FindResult;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[allow(dead_code)]
 pub struct Finder {
@@ -58,7 +58,7 @@ impl Finder {
         Ok(())
     }
 
-    fn matches(&self, path: &PathBuf) -> bool {
+    fn matches(&self, path: &Path) -> bool {
         if self.expression.is_empty() {
             return false;
         }